@@ -1 +1 @@
-cargo build && sudo KUNAI_LOGLEVEL=Debug ./target/debug/kunai
+cargo build && sudo KUNAI_LOGLEVEL=Debug ./target/debug/kunai "$@"
@@ -0,0 +1,38 @@
+use crate::{expression::quote_name, memory_model::MemoryMap};
+
+/// `libgame.so+0x1A2B30` for addresses in a module, `0x7FFC1A2B30` for everything else.
+/// Module relative addresses still work after the process restarts with a different layout.
+pub fn format_address(maps: &[MemoryMap], addr: usize) -> String {
+    match find_map(maps, addr) {
+        Some(map) => format_in_map(map, addr),
+        None => format_hex(addr),
+    }
+}
+
+/// Like `format_address`, for an address known to be in `map`
+pub fn format_in_map(map: &MemoryMap, addr: usize) -> String {
+    match map.base {
+        Some(base) => {
+            let module = map.name.rsplit('/').next().unwrap_or(&map.name);
+            format!("{}+0x{:X}", quote_name(module), addr - base)
+        }
+        None => format_hex(addr),
+    }
+}
+
+/// How an address outside any module is shown, and every address in messages
+pub fn format_hex(addr: usize) -> String {
+    format!("0x{:X}", addr)
+}
+
+/// The map holding an address, `maps` sorted by address like `read_maps` returns them
+pub fn find_map(maps: &[MemoryMap], addr: usize) -> Option<&MemoryMap> {
+    let idx = maps.partition_point(|m| m.start <= addr);
+    maps.get(idx.checked_sub(1)?).filter(|m| addr < m.end)
+}
+
+/// Hex number, the `0x` is optional
+pub fn parse_hex(input: &str) -> Result<usize, String> {
+    let digits = input.trim_start_matches("0x").trim_start_matches("0X");
+    usize::from_str_radix(digits, 16).map_err(|_| format!("'{}' is not a hex number", input))
+}
@@ -0,0 +1,263 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    address::format_address,
+    expression::{evaluate, migrate_address, EvalContext},
+    freezer::{Freeze, FreezeMode},
+    logging::get_data_dir,
+    memory_model::{MemoryMap, SearchLocation},
+    utils::{bytes_to_hex, hex_to_bytes},
+    value_type::{Endianness, ValueType},
+};
+
+/// A saved address, like a row of a cheat table
+#[derive(Debug, Clone)]
+pub struct AddressEntry {
+    pub description: String,
+    /// An address expression, module relative when possible, see `format_address`
+    pub address: String,
+    pub value_type: ValueType,
+    pub endianness: Endianness,
+    /// Bytes read for the value, the size of the type for numbers
+    pub length: usize,
+    /// Entries of a group are frozen and unfrozen together
+    pub group: String,
+    /// Where the address pointed and the value there when last refreshed, for the UI
+    pub resolved: Option<usize>,
+    pub value: String,
+}
+
+/// A frozen entry as stored in the table, with the bytes it's frozen at
+#[derive(Debug, Clone, PartialEq)]
+pub struct SavedFreeze {
+    pub mode: FreezeMode,
+    pub bytes: Vec<u8>,
+}
+
+/// The table file, entries are stored as `[[entries]]`
+#[derive(Debug, Serialize, Deserialize)]
+struct TableFile {
+    #[serde(default)]
+    entries: Vec<TableEntry>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct TableEntry {
+    #[serde(default)]
+    description: String,
+    address: String,
+    #[serde(rename = "type")]
+    value_type: String,
+    #[serde(default = "default_endianness")]
+    endianness: String,
+    #[serde(default)]
+    length: Option<usize>,
+    #[serde(default)]
+    group: String,
+    /// The value as text, written by older versions
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    frozen: Option<String>,
+    /// Hex bytes, so strings that aren't valid text survive a save
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    frozen_bytes: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    freeze_mode: Option<String>,
+}
+
+fn default_endianness() -> String {
+    Endianness::Little.to_string()
+}
+
+impl AddressEntry {
+    pub fn from_location(location: &SearchLocation, maps: &[MemoryMap]) -> AddressEntry {
+        AddressEntry {
+            description: String::new(),
+            address: format_address(maps, location.start),
+            value_type: location.value_type,
+            endianness: location.endianness,
+            length: location.end - location.start,
+            group: String::new(),
+            resolved: Some(location.start),
+            value: location.value.clone(),
+        }
+    }
+
+    pub fn resolve(&self, ctx: &EvalContext) -> Result<usize, String> {
+        evaluate(ctx, &self.address)
+    }
+
+    /// A location to hand to the value editor
+    pub fn location(&self, ctx: &EvalContext) -> Result<SearchLocation, String> {
+        let mut location = SearchLocation::new();
+        location.start = self.resolve(ctx)?;
+        location.end = location.start + self.length;
+        location.value_type = self.value_type;
+        location.endianness = self.endianness;
+        location.value = self.value.clone();
+        Ok(location)
+    }
+}
+
+/// Default table of a process, one file per process name
+pub fn table_path(process: &str) -> PathBuf {
+    get_data_dir()
+        .join("tables")
+        .join(format!("{}.toml", process))
+}
+
+/// Writes the entries and their freezes as a TOML table
+pub fn save_table(path: &Path, entries: &[(&AddressEntry, Option<Freeze>)]) -> Result<(), String> {
+    let table = TableFile {
+        entries: entries
+            .iter()
+            .map(|(entry, freeze)| TableEntry {
+                description: entry.description.clone(),
+                address: entry.address.clone(),
+                value_type: entry.value_type.to_string(),
+                endianness: entry.endianness.to_string(),
+                length: Some(entry.length),
+                group: entry.group.clone(),
+                frozen: None,
+                frozen_bytes: freeze.as_ref().map(|f| bytes_to_hex(&f.bytes)),
+                freeze_mode: freeze.as_ref().map(|f| f.mode.to_string()),
+            })
+            .collect(),
+    };
+
+    let content = toml::to_string_pretty(&table).map_err(|e| e.to_string())?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+    fs::write(path, content).map_err(|e| e.to_string())
+}
+
+/// Reads a TOML table, with the freezes it had when saved
+pub fn load_table(path: &Path) -> Result<Vec<(AddressEntry, Option<SavedFreeze>)>, String> {
+    let content = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let table: TableFile = toml::from_str(&content).map_err(|e| e.to_string())?;
+
+    let mut entries = Vec::new();
+    for e in table.entries {
+        let value_type = ValueType::from_name(&e.value_type)?;
+        let length = match (e.length, value_type.size()) {
+            (_, Some(size)) => size,
+            (Some(length), None) => length,
+            (None, None) => return Err(format!("{} needs a length", e.address)),
+        };
+
+        let endianness = Endianness::from_name(&e.endianness)?;
+        let bytes = match (e.frozen_bytes, e.frozen) {
+            (Some(hex), _) => Some(hex_to_bytes(&hex)?),
+            (None, Some(value)) => Some(value_type.encode(&value, endianness)?),
+            (None, None) => None,
+        };
+        if let (Some(bytes), Some(size)) = (&bytes, value_type.size()) {
+            if bytes.len() != size {
+                return Err(format!("{} is frozen at {} bytes", e.address, bytes.len()));
+            }
+        }
+        let freeze = match bytes {
+            Some(bytes) => Some(SavedFreeze {
+                mode: match e.freeze_mode {
+                    Some(m) => FreezeMode::from_name(&m)?,
+                    None => FreezeMode::Exact,
+                },
+                bytes,
+            }),
+            None => None,
+        };
+
+        entries.push((
+            AddressEntry {
+                description: e.description,
+                address: migrate_address(&e.address),
+                value_type,
+                endianness,
+                length,
+                group: e.group,
+                resolved: None,
+                value: String::new(),
+            },
+            freeze,
+        ));
+    }
+
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::value_type::StrEncoding;
+
+    fn entry(value_type: ValueType, length: usize) -> AddressEntry {
+        AddressEntry {
+            description: "hp".to_string(),
+            address: "game+0x10".to_string(),
+            value_type,
+            endianness: Endianness::Big,
+            length,
+            group: String::new(),
+            resolved: None,
+            value: String::new(),
+        }
+    }
+
+    #[test]
+    fn frozen_bytes_round_trip() {
+        let text = entry(ValueType::Str(StrEncoding::Utf8), 4);
+        let number = entry(ValueType::I32, 4);
+        let frozen = |e: &AddressEntry, bytes: Vec<u8>, mode| {
+            Some(Freeze::new(0, e.value_type, e.endianness, bytes, mode).unwrap())
+        };
+        let entries = [
+            (
+                &text,
+                frozen(&text, vec![0x00, 0x01, 0xff, b'a'], FreezeMode::Exact),
+            ),
+            (
+                &number,
+                frozen(&number, vec![0, 0, 0, 7], FreezeMode::NeverBelow),
+            ),
+            (&number, None),
+        ];
+
+        let path = std::env::temp_dir().join(format!("kunai-table-{}.toml", std::process::id()));
+        save_table(&path, &entries).unwrap();
+        let loaded = load_table(&path);
+        fs::remove_file(&path).unwrap();
+
+        let saved: Vec<Option<SavedFreeze>> = loaded.unwrap().into_iter().map(|(_, f)| f).collect();
+        assert_eq!(
+            saved,
+            [
+                Some(SavedFreeze {
+                    mode: FreezeMode::Exact,
+                    bytes: vec![0x00, 0x01, 0xff, b'a'],
+                }),
+                Some(SavedFreeze {
+                    mode: FreezeMode::NeverBelow,
+                    bytes: vec![0, 0, 0, 7],
+                }),
+                None,
+            ]
+        );
+    }
+
+    #[test]
+    fn old_frozen_text_is_encoded() {
+        let path = std::env::temp_dir().join(format!("kunai-old-{}.toml", std::process::id()));
+        let content = "[[entries]]\naddress = \"0x10\"\ntype = \"u16\"\nfrozen = \"258\"\n";
+        fs::write(&path, content).unwrap();
+        let loaded = load_table(&path);
+        fs::remove_file(&path).unwrap();
+
+        let (_, freeze) = loaded.unwrap().pop().unwrap();
+        assert_eq!(freeze.unwrap().bytes, vec![2, 1]);
+    }
+}
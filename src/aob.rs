@@ -0,0 +1,142 @@
+use memchr::memchr_iter;
+
+/// An array of bytes pattern like `48 8B 05 ?? ?? ?? ?? 89 4? 10`
+///
+/// Every byte has a mask, only the masked bits have to match.
+/// `??` is a full wildcard, `4?` and `?4` are nibble wildcards.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pattern {
+    pub bytes: Vec<u8>,
+    pub mask: Vec<u8>,
+}
+
+impl Pattern {
+    pub fn parse(input: &str) -> Result<Pattern, String> {
+        let mut bytes = Vec::new();
+        let mut mask = Vec::new();
+
+        for token in input.split_ascii_whitespace() {
+            // Allow `?` as a shorthand for `??`
+            let token = if token == "?" { "??" } else { token };
+
+            let nibbles: Vec<char> = token.chars().collect();
+            if nibbles.len() != 2 {
+                return Err(format!("'{}' is not a byte", token));
+            }
+
+            let (high, high_mask) = parse_nibble(nibbles[0])?;
+            let (low, low_mask) = parse_nibble(nibbles[1])?;
+
+            bytes.push(high << 4 | low);
+            mask.push(high_mask << 4 | low_mask);
+        }
+
+        if bytes.is_empty() {
+            return Err("Empty pattern".to_string());
+        }
+
+        Ok(Pattern { bytes, mask })
+    }
+
+    pub fn has_wildcards(&self) -> bool {
+        self.mask.iter().any(|m| *m != 0xff)
+    }
+
+    pub fn matches_at(&self, haystack: &[u8], pos: usize) -> bool {
+        if pos + self.bytes.len() > haystack.len() {
+            return false;
+        }
+
+        haystack[pos..pos + self.bytes.len()]
+            .iter()
+            .zip(self.bytes.iter().zip(&self.mask))
+            .all(|(h, (b, m))| h & m == b & m)
+    }
+
+    /// Returns the offsets of every match in the haystack
+    pub fn find_all(&self, haystack: &[u8]) -> Vec<usize> {
+        // Use the first fully known byte as an anchor for memchr
+        let anchor = match self.mask.iter().position(|m| *m == 0xff) {
+            Some(a) => a,
+            None => {
+                return (0..haystack.len())
+                    .filter(|pos| self.matches_at(haystack, *pos))
+                    .collect()
+            }
+        };
+
+        memchr_iter(self.bytes[anchor], haystack)
+            .filter_map(|i| i.checked_sub(anchor))
+            .filter(|pos| self.matches_at(haystack, *pos))
+            .collect()
+    }
+}
+
+/// Returns the nibble value and its mask
+fn parse_nibble(c: char) -> Result<(u8, u8), String> {
+    if c == '?' {
+        return Ok((0, 0));
+    }
+
+    match c.to_digit(16) {
+        Some(n) => Ok((n as u8, 0xf)),
+        None => Err(format!("'{}' is not a hex digit", c)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn find(pattern: &str, haystack: &[u8]) -> Vec<usize> {
+        Pattern::parse(pattern).unwrap().find_all(haystack)
+    }
+
+    #[test]
+    fn full_wildcards() {
+        let p = Pattern::parse("48 ?? 05 ?").unwrap();
+        assert_eq!(p.bytes, [0x48, 0x00, 0x05, 0x00]);
+        assert_eq!(p.mask, [0xff, 0x00, 0xff, 0x00]);
+        assert!(p.has_wildcards());
+        assert!(!Pattern::parse("48 8b").unwrap().has_wildcards());
+
+        assert_eq!(
+            find("48 ?? 05", &[0x48, 0x8b, 0x05, 0x48, 0x00, 0x05]),
+            [0, 3]
+        );
+    }
+
+    #[test]
+    fn nibble_masks() {
+        let p = Pattern::parse("4? ?4").unwrap();
+        assert_eq!(p.bytes, [0x40, 0x04]);
+        assert_eq!(p.mask, [0xf0, 0x0f]);
+
+        assert_eq!(find("4?", &[0x40, 0x4f, 0x50, 0x04]), [0, 1]);
+        assert_eq!(find("?4", &[0x40, 0x04, 0xf4, 0x45]), [1, 2]);
+    }
+
+    #[test]
+    fn only_wildcards() {
+        assert_eq!(find("?? ??", &[1, 2, 3]), [0, 1]);
+        assert_eq!(find("??", &[]), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn invalid_tokens() {
+        assert!(Pattern::parse("").is_err());
+        assert!(Pattern::parse("   ").is_err());
+        assert!(Pattern::parse("4").is_err());
+        assert!(Pattern::parse("488b").is_err());
+        assert!(Pattern::parse("4g").is_err());
+        assert!(Pattern::parse("???").is_err());
+    }
+
+    #[test]
+    fn match_on_the_last_byte() {
+        assert_eq!(find("02 ?? 04", &[1, 2, 3, 4]), [1]);
+        assert_eq!(find("?? 04", &[1, 2, 3, 4]), [2]);
+        // Would run past the end
+        assert_eq!(find("04 ??", &[1, 2, 3, 4]), Vec::<usize>::new());
+    }
+}
@@ -0,0 +1,110 @@
+use std::{
+    fs::{self, OpenOptions},
+    io::Write,
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::Serialize;
+use toml::value::{Date, Datetime, Offset, Time};
+
+use crate::logging::get_data_dir;
+
+/// A write to the memory of a process, as kept in the audit log
+#[derive(Debug, Serialize)]
+pub struct AuditEntry {
+    pub time: Datetime,
+    pub uid: u32,
+    /// Who ran kunai through sudo, if they did
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sudo_uid: Option<u32>,
+    pub pid: String,
+    pub process: String,
+    /// `edit`, `hex edit`, `undo`, `redo` or `freeze`
+    pub kind: String,
+    pub address: String,
+    /// Module and offset, when the address is in a module
+    pub location: String,
+    pub old: String,
+    pub new: String,
+    /// How many times a frozen value was rewritten since the last entry for it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rewrites: Option<u64>,
+}
+
+/// TOML needs a table around the entries
+#[derive(Serialize)]
+struct AuditFile<'a> {
+    writes: Vec<&'a AuditEntry>,
+}
+
+/// Kept apart from the log file, which starts over on every run
+pub fn audit_path() -> PathBuf {
+    get_data_dir().join("audit.toml")
+}
+
+/// Appends the entry to the audit log, never touching what's already in it
+pub fn append_entry(entry: &AuditEntry) -> Result<(), String> {
+    let content = toml::to_string(&AuditFile {
+        writes: vec![entry],
+    })
+    .map_err(|e| e.to_string())?;
+
+    let path = audit_path();
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .map_err(|e| format!("Can't open {}: {}", path.display(), e))?;
+    file.write_all(format!("\n{}", content).as_bytes())
+        .map_err(|e| format!("Can't write to {}: {}", path.display(), e))
+}
+
+/// The real uid, and the one sudo was run from
+pub fn current_uids() -> (u32, Option<u32>) {
+    // SAFETY: getuid can't fail
+    let uid = unsafe { libc::getuid() };
+    let sudo_uid = std::env::var("SUDO_UID").ok().and_then(|u| u.parse().ok());
+    (uid, sudo_uid)
+}
+
+/// The current time in UTC
+pub fn now() -> Datetime {
+    let since_epoch = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let secs = since_epoch.as_secs();
+    let (year, month, day) = civil_from_days((secs / 86400) as i64);
+    let secs_of_day = secs % 86400;
+
+    Datetime {
+        date: Some(Date { year, month, day }),
+        time: Some(Time {
+            hour: (secs_of_day / 3600) as u8,
+            minute: (secs_of_day % 3600 / 60) as u8,
+            second: (secs_of_day % 60) as u8,
+            nanosecond: since_epoch.subsec_millis() * 1_000_000,
+        }),
+        offset: Some(Offset::Z),
+    }
+}
+
+/// Year, month and day of a day counted from 1970-01-01, from
+/// http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+fn civil_from_days(days: i64) -> (u16, u8, u8) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    (year as u16, month as u8, day as u8)
+}
@@ -1,15 +1,25 @@
 use ratatui::{
-    layout::{Constraint, Direction, Layout, Rect},
+    layout::{Alignment, Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style, Stylize},
     text::Text,
-    widgets::{Block, Borders, Clear, Padding, Paragraph, Row, Table},
+    widgets::{
+        block::{Position, Title},
+        Block, Borders, Clear, Gauge, Paragraph, Row, Table, Wrap,
+    },
     Frame,
 };
 
 use crate::{
+    address::{format_address, format_in_map},
+    address_list::table_path,
+    components::{
+        hex_view::render_hex_view, journal::render_journal, struct_view::render_struct_view,
+    },
     kunai::Kunai,
+    scan_worker::ScanJob,
     ui::SubScreen,
-    utils::{centered_rect, num_to_hex},
+    utils::{bytes_to_hex, centered_rect, human_bytes},
+    value_type::ValueType,
 };
 
 pub fn render_memory_editor(frame: &mut Frame, body_rect: Rect, kunai: &mut Kunai) {
@@ -18,13 +28,51 @@ pub fn render_memory_editor(frame: &mut Frame, body_rect: Rect, kunai: &mut Kuna
         .constraints([Constraint::Min(5), Constraint::Percentage(100)])
         .split(body_rect);
 
-    let search_string_block = Paragraph::new(kunai.memedit.search_string.to_string())
-        .block(
+    let search_title = match kunai.memedit.value_type {
+        ValueType::Aob => format!(
+            "Enter AOB Pattern [{} maps | align {}]",
+            match kunai.memedit.aob_executable_only {
+                true => "executable",
+                false => "all",
+            },
+            kunai.memedit.alignment
+        ),
+        ValueType::Regex => format!("Enter Regex [align {}]", kunai.memedit.alignment),
+        ValueType::Str(encoding) => format!(
+            "Enter Search String [{} | {} | align {}]",
+            encoding,
+            match kunai.memedit.case_insensitive {
+                true => "ignore case",
+                false => "match case",
+            },
+            kunai.memedit.alignment
+        ),
+        _ => format!(
+            "Enter Search String [{} | {} | align {}]",
+            kunai.memedit.value_type, kunai.memedit.endianness, kunai.memedit.alignment
+        ),
+    };
+
+    let next_scan_title = format!(
+        "Next scan: {} ({})",
+        kunai.memedit.compare_mode, kunai.memedit.compare_against
+    );
+
+    let search_string_block = match &kunai.memedit.goto_input {
+        Some(input) => Paragraph::new(input.to_string()).block(
             Block::new()
                 .borders(Borders::ALL)
-                .title("Enter Search String"),
-        )
-        .style(Style::default());
+                .border_style(Style::default().fg(Color::Yellow))
+                .title("Go to address, e.g. [[module+0x1000]+0x18]+4 (Enter to go, Esc to cancel)"),
+        ),
+        None => Paragraph::new(kunai.memedit.search_string.to_string()).block(
+            Block::new()
+                .borders(Borders::ALL)
+                .title(search_title)
+                .title(Title::from(next_scan_title).alignment(Alignment::Right)),
+        ),
+    }
+    .style(Style::default());
 
     frame.render_widget(search_string_block, body_chunks[0]);
 
@@ -34,70 +82,212 @@ pub fn render_memory_editor(frame: &mut Frame, body_rect: Rect, kunai: &mut Kuna
         render_maps_table(frame, body_rect, kunai);
     } else if kunai.memedit.sub_screen == SubScreen::ValueEditing {
         render_value_editor(frame, body_rect, kunai);
+    } else if kunai.memedit.sub_screen == SubScreen::PointerScan {
+        render_pointer_scan(frame, body_rect, kunai);
+    } else if kunai.memedit.sub_screen == SubScreen::AddressList {
+        render_address_list(frame, body_rect, kunai);
+    } else if kunai.memedit.sub_screen == SubScreen::HexView {
+        render_hex_view(frame, body_rect, kunai);
+    } else if kunai.memedit.sub_screen == SubScreen::StructView {
+        render_struct_view(frame, body_rect, kunai);
+    } else if kunai.memedit.sub_screen == SubScreen::Journal {
+        render_journal(frame, body_rect, kunai);
+    }
+
+    if let Some(job) = &kunai.memedit.scan_job {
+        render_scan_progress(frame, body_rect, job);
     }
 }
 
+fn render_scan_progress(frame: &mut Frame, body_rect: Rect, job: &ScanJob) {
+    let area = centered_rect(60, 20, body_rect);
+    let progress = &job.progress;
+
+    let eta = match progress.eta() {
+        Some(eta) => format!("{}s", eta.as_secs()),
+        None => "?".to_string(),
+    };
+
+    let label = format!(
+        "{}/{} maps | {} / {} | {} hits | ETA {}",
+        progress.maps_done,
+        progress.maps_total,
+        human_bytes(progress.bytes_scanned),
+        human_bytes(progress.bytes_total),
+        progress.hits,
+        eta
+    );
+
+    let title = match job.is_cancelled() {
+        true => "Cancelling...",
+        false => "Scanning (Esc to cancel)",
+    };
+
+    let gauge = Gauge::default()
+        .block(
+            Block::default()
+                .title(title)
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(Color::Yellow)),
+        )
+        .gauge_style(Style::default().fg(Color::Green).bg(Color::Black))
+        .ratio(progress.ratio())
+        .label(label);
+
+    frame.render_widget(Clear, area);
+    frame.render_widget(gauge, area);
+}
+
 pub fn render_value_editor(frame: &mut Frame, popup_rect: Rect, kunai: &mut Kunai) {
     let area = centered_rect(40, 60, popup_rect);
 
     let editor_chunks = Layout::default()
         .direction(Direction::Vertical)
-        .constraints([Constraint::Min(4), Constraint::Percentage(100)])
+        .constraints([
+            Constraint::Min(4),
+            Constraint::Length(3),
+            Constraint::Percentage(100),
+        ])
         .split(area);
 
-    let selected_value = match &kunai.memedit.selected_value {
-        Some(loc) => loc.value.to_string(),
-        None => "None".to_string(),
+    let memedit = &kunai.memedit;
+    let (selected_value, selected_title) = match &memedit.selected_value {
+        Some(loc) => (
+            loc.value.to_string(),
+            format!(
+                "Selected Value at {}",
+                format_address(&memedit.task_mem.maps, loc.start)
+            ),
+        ),
+        None => ("None".to_string(), "Selected Value".to_string()),
     };
 
     let curr_value = Paragraph::new(Text::styled(selected_value, Style::default().dark_gray()))
         .style(Style::default())
-        .block(
-            Block::default()
-                .borders(Borders::ALL)
-                .title("Selected Value"),
-        );
+        .block(Block::default().borders(Borders::ALL).title(selected_title));
 
+    let new_value_title = match &memedit.selected_value {
+        Some(loc) if loc.value_type.is_numeric() => {
+            format!("New Value ({} {})", loc.value_type, loc.endianness)
+        }
+        Some(loc) if matches!(loc.value_type, ValueType::Str(_) | ValueType::Regex) => {
+            format!("New Value ({}, {})", loc.value_type, memedit.str_fill)
+        }
+        Some(loc) => format!("New Value ({})", loc.value_type),
+        None => "New Value".to_string(),
+    };
     let new_value = Paragraph::new(Text::styled(
-        &kunai.memedit.new_value,
+        &memedit.new_value,
         Style::default().slow_blink(),
     ))
     .style(Style::default())
-    .block(Block::default().borders(Borders::ALL).title("New Value"));
+    .block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(new_value_title),
+    );
+
+    let preview_text = match memedit.new_value_bytes() {
+        Ok(bytes) => Text::from(format!(
+            "{} ({} of {} bytes)",
+            bytes_to_hex(&bytes),
+            bytes.len(),
+            memedit
+                .selected_value
+                .as_ref()
+                .map_or(0, |l| l.end - l.start)
+        )),
+        Err(e) => Text::styled(e, Style::default().fg(Color::Red)),
+    };
+    let keys = match memedit.selected_value.as_ref().map(|l| l.value_type) {
+        Some(ValueType::Str(_) | ValueType::Regex) => {
+            "Enter write | Ctrl+f freeze | Ctrl+n short strings | Esc back"
+        }
+        _ => "Enter write | Ctrl+f freeze | Esc back",
+    };
+    let preview = Paragraph::new(preview_text)
+        .wrap(Wrap { trim: false })
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title("Bytes to write")
+                .title(
+                    Title::from(keys)
+                        .alignment(Alignment::Right)
+                        .position(Position::Bottom),
+                ),
+        );
 
     frame.render_widget(Clear, area);
 
     frame.render_widget(curr_value, editor_chunks[0]);
     frame.render_widget(new_value, editor_chunks[1]);
+    frame.render_widget(preview, editor_chunks[2]);
 }
 
 fn render_search_table(frame: &mut Frame, table_rect: Rect, kunai: &mut Kunai) {
-    let table_header = Row::new(vec!["START", "END", "VALUE", "PREVIOUS VALUE", "MEM NAME"])
-        .style(Style::new().bold())
-        .bottom_margin(1);
+    let table_header = Row::new(vec![
+        "START",
+        "END",
+        "TYPE",
+        "VALUE",
+        "PREVIOUS VALUE",
+        "FROZEN",
+        "MEM NAME",
+    ])
+    .style(Style::new().bold())
+    .bottom_margin(1);
     let column_widhts = [
         Constraint::Min(10),
         Constraint::Min(10),
-        Constraint::Percentage(40),
+        Constraint::Length(8),
+        Constraint::Percentage(30),
         Constraint::Percentage(20),
         Constraint::Percentage(10),
+        Constraint::Percentage(10),
     ];
 
     let mut search_locations = Vec::new();
 
     for loc in &kunai.memedit.search_list {
         let row = Row::new(vec![
-            num_to_hex(loc.start as i64),
-            num_to_hex(loc.end as i64),
+            format_in_map(&loc.mem_info, loc.start),
+            format_in_map(&loc.mem_info, loc.end),
+            loc.value_type.to_string(),
             loc.value.to_string(),
             loc.prev_value.to_string(),
+            kunai
+                .memedit
+                .freezer
+                .as_ref()
+                .and_then(|f| f.get(loc.start))
+                .map_or(String::new(), |f| f.label()),
             loc.mem_info.name.to_string(),
         ]);
 
         search_locations.push(row);
     }
 
-    let search_block = Block::default().style(Style::new()).borders(Borders::ALL);
+    let mut search_block = Block::default().style(Style::new()).borders(Borders::ALL);
+
+    // Unknown value scans have too many candidates to show them all
+    if let Some(snapshot) = &kunai.memedit.snapshot {
+        search_block = search_block.title(format!(
+            "Snapshot: {} candidates in {} regions (next scan to narrow down)",
+            snapshot.candidates(),
+            snapshot.regions.len()
+        ));
+    }
+
+    if let Some(freezer) = &kunai.memedit.freezer {
+        let freeze_title = format!(
+            "Freeze: {} every {}ms ({} frozen)",
+            kunai.memedit.freeze_mode,
+            freezer.interval(),
+            freezer.count()
+        );
+        search_block = search_block.title(Title::from(freeze_title).alignment(Alignment::Right));
+    }
 
     let table = Table::new(search_locations, column_widhts)
         .column_spacing(1)
@@ -127,8 +317,8 @@ fn render_maps_table(frame: &mut Frame, body_rect: Rect, kunai: &mut Kunai) {
                 true => "Y".to_string(),
                 false => "N".to_string(),
             },
-            num_to_hex(m.start as i64),
-            num_to_hex(m.end as i64),
+            format_in_map(m, m.start),
+            format_in_map(m, m.end),
             m.perms.to_string(),
             m.name.to_string(),
         ]));
@@ -142,8 +332,27 @@ fn render_maps_table(frame: &mut Frame, body_rect: Rect, kunai: &mut Kunai) {
         Constraint::Percentage(40),
     ];
 
+    let memedit = &kunai.memedit;
+    let title = match &memedit.map_glob {
+        Some(glob) => format!(
+            "Maps - name glob: {}_ (Enter to apply, Esc to cancel)",
+            glob
+        ),
+        None => format!(
+            "Maps ({}/{} selected)",
+            memedit.task_mem.selected_count(),
+            memedit.task_mem.maps.len()
+        ),
+    };
+    let keys = "Enter/Space toggle | a all | n none | w rw-p | f no files | h heap/stack/anon | o this module | / glob";
+
     let maps_block = Block::default()
-        .title("Maps")
+        .title(title)
+        .title(
+            Title::from(keys)
+                .alignment(Alignment::Right)
+                .position(Position::Bottom),
+        )
         .borders(Borders::ALL)
         .border_style(Style::default().fg(Color::Yellow))
         .style(Style::default());
@@ -166,3 +375,162 @@ fn render_maps_table(frame: &mut Frame, body_rect: Rect, kunai: &mut Kunai) {
 
     frame.render_stateful_widget(table, area, &mut kunai.memedit.map_table_state);
 }
+
+fn render_pointer_scan(frame: &mut Frame, body_rect: Rect, kunai: &mut Kunai) {
+    let memedit = &kunai.memedit;
+    let config = &memedit.pointer_config;
+
+    let title = format!(
+        "Pointers to {} [depth {} | offset 0x{:X} | {} bit] ({} chains)",
+        memedit
+            .pointer_target
+            .as_ref()
+            .map_or("?".to_string(), |t| format_address(
+                &memedit.task_mem.maps,
+                t.start
+            )),
+        config.max_depth,
+        config.max_offset,
+        config.width * 8,
+        memedit.pointer_chains.len()
+    );
+    let keys = "Enter scan | d max depth | o max offset | r follow chains | a add to list | s save | c compare with saved | Esc back";
+
+    let rows: Vec<Row> = memedit
+        .pointer_chains
+        .iter()
+        .enumerate()
+        .map(|(i, chain)| {
+            let resolved = match memedit.pointer_resolved.get(i) {
+                Some(Some(addr)) => format_address(&memedit.task_mem.maps, *addr),
+                Some(None) => "broken".to_string(),
+                None => String::new(),
+            };
+            Row::new(vec![
+                chain.offsets.len().to_string(),
+                chain.to_string(),
+                resolved,
+            ])
+        })
+        .collect();
+
+    let pointer_block = Block::default()
+        .title(title)
+        .title(
+            Title::from(keys)
+                .alignment(Alignment::Right)
+                .position(Position::Bottom),
+        )
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Yellow));
+
+    let column_widhts = [
+        Constraint::Length(5),
+        Constraint::Percentage(80),
+        Constraint::Percentage(20),
+    ];
+
+    let table = Table::new(rows, column_widhts)
+        .column_spacing(1)
+        .style(Style::default().bg(Color::Black))
+        .header(
+            Row::new(vec!["DEPTH", "CHAIN", "RESOLVES TO"])
+                .style(Style::new().bold())
+                .bottom_margin(1),
+        )
+        .block(pointer_block)
+        .highlight_style(
+            Style::new()
+                .add_modifier(Modifier::BOLD)
+                .fg(Color::Black)
+                .bg(Color::Gray),
+        );
+
+    let area = centered_rect(80, 90, body_rect);
+
+    frame.render_widget(Clear, area);
+    frame.render_stateful_widget(table, area, &mut kunai.memedit.pointer_table_state);
+}
+
+fn render_address_list(frame: &mut Frame, body_rect: Rect, kunai: &mut Kunai) {
+    let memedit = &kunai.memedit;
+
+    let title = match &memedit.address_input {
+        Some(input) => format!(
+            "Address list - description: {}_ (Enter to apply, Esc to cancel)",
+            input
+        ),
+        None => format!(
+            "Address list ({} entries) - {}",
+            memedit.address_list.len(),
+            table_path(&memedit.task.name).display()
+        ),
+    };
+    let keys = "Enter edit | h hex view | f freeze | g freeze group | 1-9 group | 0 ungroup | d describe | x remove | s save | l load | Esc back";
+
+    let rows: Vec<Row> = memedit
+        .address_list
+        .iter()
+        .map(|entry| {
+            let frozen = match (&memedit.freezer, entry.resolved) {
+                (Some(freezer), Some(addr)) => freezer.get(addr).map(|f| f.label()),
+                _ => None,
+            };
+            Row::new(vec![
+                entry.group.to_string(),
+                entry.description.to_string(),
+                entry.address.to_string(),
+                entry.value_type.to_string(),
+                entry.value.to_string(),
+                frozen.unwrap_or_default(),
+            ])
+        })
+        .collect();
+
+    let address_block = Block::default()
+        .title(title)
+        .title(
+            Title::from(keys)
+                .alignment(Alignment::Right)
+                .position(Position::Bottom),
+        )
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Yellow));
+
+    let column_widhts = [
+        Constraint::Length(5),
+        Constraint::Percentage(25),
+        Constraint::Percentage(25),
+        Constraint::Length(8),
+        Constraint::Percentage(25),
+        Constraint::Percentage(15),
+    ];
+
+    let table = Table::new(rows, column_widhts)
+        .column_spacing(1)
+        .style(Style::default().bg(Color::Black))
+        .header(
+            Row::new(vec![
+                "GROUP",
+                "DESCRIPTION",
+                "ADDRESS",
+                "TYPE",
+                "VALUE",
+                "FROZEN",
+            ])
+            .style(Style::new().bold())
+            .bottom_margin(1),
+        )
+        .block(address_block)
+        .highlight_style(
+            Style::new()
+                .add_modifier(Modifier::BOLD)
+                .fg(Color::Black)
+                .bg(Color::Gray),
+        );
+
+    let area = centered_rect(80, 90, body_rect);
+
+    frame.render_widget(Clear, area);
+    frame.render_stateful_widget(table, area, &mut kunai.memedit.address_table_state);
+}
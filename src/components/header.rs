@@ -1,12 +1,16 @@
 use ratatui::{
     layout::{Constraint, Direction, Layout, Rect},
-    style::{Color, Style},
+    style::{Color, Style, Stylize},
     text::Text,
     widgets::{Block, Borders, Paragraph},
     Frame,
 };
 
-use crate::{kunai::Kunai, ui::CurrentScreen};
+use crate::{
+    kunai::Kunai,
+    proc_mem::{is_read_only, is_read_only_locked},
+    ui::CurrentScreen,
+};
 
 pub fn render_header(frame: &mut Frame, header_rect: Rect, kunai: &mut Kunai) {
     match kunai.current_screen {
@@ -21,7 +25,10 @@ fn task_screen_header(frame: &mut Frame, header_rect: Rect, kunai: &mut Kunai) {
         .style(Style::default());
 
     let title = Paragraph::new(Text::styled(
-        "KUNAI (A process memory editor)",
+        match is_read_only() {
+            true => "KUNAI (A process memory editor) [READ-ONLY]",
+            false => "KUNAI (A process memory editor)",
+        },
         Style::default().fg(Color::Green),
     ))
     .block(title_block);
@@ -61,5 +68,26 @@ fn memedit_screen_header(frame: &mut Frame, header_rect: Rect, kunai: &mut Kunai
     ))
     .block(Block::default().borders(Borders::ALL));
 
-    frame.render_widget(proc_info, header_rect);
+    let mode = match (is_read_only(), is_read_only_locked()) {
+        (true, true) => Text::styled("READ-ONLY (--read-only)", Style::default().red().bold()),
+        (true, false) => Text::styled(
+            "READ-ONLY (F2 to allow writes)",
+            Style::default().red().bold(),
+        ),
+        (false, _) => Text::styled(
+            "Writes allowed (F2 for read-only)",
+            Style::default().dark_gray(),
+        ),
+    };
+    let write_mode = Paragraph::new(mode)
+        .block(Block::default().borders(Borders::ALL))
+        .right_aligned();
+
+    let header_chunk = Layout::new(
+        Direction::Horizontal,
+        [Constraint::Percentage(65), Constraint::Percentage(35)],
+    )
+    .split(header_rect);
+    frame.render_widget(proc_info, header_chunk[0]);
+    frame.render_widget(write_mode, header_chunk[1]);
 }
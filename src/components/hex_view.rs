@@ -0,0 +1,124 @@
+use ratatui::{
+    layout::{Alignment, Rect},
+    style::{Color, Modifier, Style, Stylize},
+    text::{Line, Span},
+    widgets::{
+        block::{Position, Title},
+        Block, Borders, Clear, Paragraph,
+    },
+    Frame,
+};
+
+use crate::{
+    address::{find_map, format_address},
+    hex_view::ROW_BYTES,
+    kunai::Kunai,
+    utils::centered_rect,
+};
+
+/// Width of the address column
+const ADDRESS_WIDTH: usize = 28;
+
+pub fn render_hex_view(frame: &mut Frame, body_rect: Rect, kunai: &mut Kunai) {
+    let area = centered_rect(80, 90, body_rect);
+    // Borders and the header row
+    kunai.memedit.hex_view.rows = area.height.saturating_sub(3) as usize;
+
+    let memedit = &kunai.memedit;
+    let view = &memedit.hex_view;
+    let maps = &memedit.task_mem.maps;
+
+    let title = match &memedit.goto_input {
+        Some(input) => format!("Go to: {}_ (Enter to go, Esc to cancel)", input),
+        None => {
+            let map = match find_map(maps, view.cursor) {
+                Some(m) => format!("{} {}", m.perms, m.name),
+                None => "not mapped".to_string(),
+            };
+            format!(
+                "Hex view at {} [{}]",
+                format_address(maps, view.cursor),
+                map
+            )
+        }
+    };
+    let keys = "arrows move | PgUp/PgDn page | 0-9 a-f overwrite | u undo | r redo | g go to | s dissect as struct | Esc back";
+
+    let mut header = format!("{:<width$}", "ADDRESS", width = ADDRESS_WIDTH);
+    for i in 0..ROW_BYTES {
+        header += &format!("{:02X} ", i);
+        if i == ROW_BYTES / 2 - 1 {
+            header.push(' ');
+        }
+    }
+    header += " ASCII";
+
+    let mut lines = vec![Line::from(Span::styled(header, Style::new().bold()))];
+
+    let cursor_style = Style::new()
+        .add_modifier(Modifier::BOLD)
+        .fg(Color::Black)
+        .bg(Color::Gray);
+    let unreadable_style = Style::new().fg(Color::Red);
+
+    for row in 0..view.rows {
+        let row_addr = view.top + row * ROW_BYTES;
+        let mut hex = vec![Span::raw(format!(
+            "{:<width$}",
+            format_address(maps, row_addr),
+            width = ADDRESS_WIDTH
+        ))];
+        let mut ascii = vec![Span::raw(" ")];
+
+        for i in 0..ROW_BYTES {
+            let addr = row_addr + i;
+            let is_cursor = addr == view.cursor;
+
+            let (text, ch, style) = match view.byte_at(addr) {
+                Some(b) => {
+                    let text = match (is_cursor, view.nibble) {
+                        (true, Some(high)) => format!("{:X}_", high),
+                        _ => format!("{:02X}", b),
+                    };
+                    let ch = match b.is_ascii_graphic() || b == b' ' {
+                        true => b as char,
+                        false => '.',
+                    };
+                    (text, ch, Style::new())
+                }
+                None => ("??".to_string(), '?', unreadable_style),
+            };
+
+            let style = match is_cursor {
+                true => cursor_style,
+                false => style,
+            };
+            hex.push(Span::styled(text, style));
+            hex.push(Span::raw(" "));
+            if i == ROW_BYTES / 2 - 1 {
+                hex.push(Span::raw(" "));
+            }
+            ascii.push(Span::styled(ch.to_string(), style));
+        }
+
+        hex.extend(ascii);
+        lines.push(Line::from(hex));
+    }
+
+    let block = Block::default()
+        .title(title)
+        .title(
+            Title::from(keys)
+                .alignment(Alignment::Right)
+                .position(Position::Bottom),
+        )
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Yellow));
+
+    let dump = Paragraph::new(lines)
+        .style(Style::default().bg(Color::Black))
+        .block(block);
+
+    frame.render_widget(Clear, area);
+    frame.render_widget(dump, area);
+}
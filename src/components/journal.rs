@@ -0,0 +1,86 @@
+use ratatui::{
+    layout::{Alignment, Constraint, Rect},
+    style::{Color, Modifier, Style, Stylize},
+    widgets::{
+        block::{Position, Title},
+        Block, Borders, Clear, Row, Table,
+    },
+    Frame,
+};
+
+use crate::{
+    address::format_address,
+    kunai::Kunai,
+    utils::{bytes_to_hex, centered_rect},
+};
+
+pub fn render_journal(frame: &mut Frame, body_rect: Rect, kunai: &mut Kunai) {
+    let memedit = &kunai.memedit;
+    let journal = &memedit.journal;
+
+    let title = format!(
+        "Writes ({} in place, {} undone)",
+        journal.done.len(),
+        journal.undone.len()
+    );
+    let keys = "u undo | r redo | a undo all | Enter undo down to / redo up to | Esc back";
+
+    let rows: Vec<Row> = journal
+        .rows()
+        .map(|(entry, in_place)| {
+            let row = Row::new(vec![
+                format_address(&memedit.task_mem.maps, entry.addr),
+                entry.new.len().to_string(),
+                bytes_to_hex(&entry.old),
+                bytes_to_hex(&entry.new),
+                match in_place {
+                    true => "written".to_string(),
+                    false => "undone".to_string(),
+                },
+            ]);
+            match in_place {
+                true => row,
+                false => row.style(Style::default().dark_gray()),
+            }
+        })
+        .collect();
+
+    let journal_block = Block::default()
+        .title(title)
+        .title(
+            Title::from(keys)
+                .alignment(Alignment::Right)
+                .position(Position::Bottom),
+        )
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Yellow));
+
+    let column_widhts = [
+        Constraint::Percentage(25),
+        Constraint::Length(6),
+        Constraint::Percentage(30),
+        Constraint::Percentage(30),
+        Constraint::Length(8),
+    ];
+
+    let table = Table::new(rows, column_widhts)
+        .column_spacing(1)
+        .style(Style::default().bg(Color::Black))
+        .header(
+            Row::new(vec!["ADDRESS", "SIZE", "OLD BYTES", "NEW BYTES", "STATE"])
+                .style(Style::new().bold())
+                .bottom_margin(1),
+        )
+        .block(journal_block)
+        .highlight_style(
+            Style::new()
+                .add_modifier(Modifier::BOLD)
+                .fg(Color::Black)
+                .bg(Color::Gray),
+        );
+
+    let area = centered_rect(80, 90, body_rect);
+
+    frame.render_widget(Clear, area);
+    frame.render_stateful_widget(table, area, &mut kunai.memedit.journal.table_state);
+}
@@ -1,4 +1,7 @@
 pub mod edit_memory;
 pub mod footer;
 pub mod header;
+pub mod hex_view;
+pub mod journal;
+pub mod struct_view;
 pub mod task_selector;
@@ -0,0 +1,94 @@
+use ratatui::{
+    layout::{Alignment, Constraint, Rect},
+    style::{Color, Modifier, Style, Stylize},
+    widgets::{
+        block::{Position, Title},
+        Block, Borders, Clear, Row, Table,
+    },
+    Frame,
+};
+
+use crate::{address::format_address, kunai::Kunai, utils::centered_rect};
+
+pub fn render_struct_view(frame: &mut Frame, body_rect: Rect, kunai: &mut Kunai) {
+    let memedit = &kunai.memedit;
+    let view = &memedit.struct_view;
+    let maps = &memedit.task_mem.maps;
+
+    let title = match (&memedit.goto_input, view.addr) {
+        (Some(input), _) => format!("Go to: {}_ (Enter to go, Esc to cancel)", input),
+        (None, Some(addr)) => format!(
+            "{}{} at {}",
+            memedit
+                .struct_defs
+                .get(view.def)
+                .map_or("No struct", |d| d.name.as_str()),
+            match memedit.struct_defs.get(view.def).is_some_and(|d| d.draft) {
+                true => " (draft)",
+                false => "",
+            },
+            format_address(maps, addr)
+        ),
+        (None, None) => "Struct view".to_string(),
+    };
+    let keys = "Enter expand/edit | t next struct | r reload | a auto dissect | y retype | s save draft | h hex view | g go to | Esc back";
+
+    let rows: Vec<Row> = view
+        .rows
+        .iter()
+        .map(|row| {
+            let marker = match (row.expandable, row.expanded) {
+                (true, true) => "v ",
+                (true, false) => "> ",
+                (false, _) => "  ",
+            };
+            Row::new(vec![
+                format!("{}{}{}", "  ".repeat(row.depth), marker, row.name),
+                row.type_name.to_string(),
+                format!("+0x{:X}", row.offset),
+                row.addr
+                    .map_or("??".to_string(), |a| format_address(maps, a)),
+                row.value.to_string(),
+            ])
+        })
+        .collect();
+
+    let struct_block = Block::default()
+        .title(title)
+        .title(
+            Title::from(keys)
+                .alignment(Alignment::Right)
+                .position(Position::Bottom),
+        )
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Yellow));
+
+    let column_widhts = [
+        Constraint::Percentage(30),
+        Constraint::Percentage(10),
+        Constraint::Length(8),
+        Constraint::Percentage(25),
+        Constraint::Percentage(35),
+    ];
+
+    let table = Table::new(rows, column_widhts)
+        .column_spacing(1)
+        .style(Style::default().bg(Color::Black))
+        .header(
+            Row::new(vec!["FIELD", "TYPE", "OFFSET", "ADDRESS", "VALUE"])
+                .style(Style::new().bold())
+                .bottom_margin(1),
+        )
+        .block(struct_block)
+        .highlight_style(
+            Style::new()
+                .add_modifier(Modifier::BOLD)
+                .fg(Color::Black)
+                .bg(Color::Gray),
+        );
+
+    let area = centered_rect(80, 90, body_rect);
+
+    frame.render_widget(Clear, area);
+    frame.render_stateful_widget(table, area, &mut kunai.memedit.struct_view.table_state);
+}
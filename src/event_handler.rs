@@ -1,14 +1,24 @@
-use core::panic;
+use std::time::Duration;
 
 use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyModifiers};
 
 use crate::{
+    hex_view::ROW_BYTES,
     kunai::Kunai,
-    ui::{self, CurrentScreen, SubScreen},
+    memory_model::MapPreset,
+    ui::{CurrentScreen, SubScreen},
 };
 
+/// How long to wait for a key before redrawing (background scans report progress meanwhile)
+const TICK: Duration = Duration::from_millis(100);
+
 // Returns a boolean to justify further processing of events
 pub fn handle_keypress(kunai: &mut Kunai) -> bool {
+    match event::poll(TICK) {
+        Ok(true) => {}
+        _ => return true,
+    }
+
     match event::read() {
         Ok(e) => {
             if let Event::Key(key) = e {
@@ -59,14 +69,12 @@ fn handle_taskselection(kunai: &mut Kunai, key: KeyEvent) -> bool {
                 return false;
             }
         }
-        KeyCode::Backspace => {
-            if kunai.tasks.name_search || kunai.tasks.pid_search {
-                kunai.tasks.search_string.pop();
-                kunai.tasks.update_filtered_list();
+        KeyCode::Backspace if kunai.tasks.name_search || kunai.tasks.pid_search => {
+            kunai.tasks.search_string.pop();
+            kunai.tasks.update_filtered_list();
 
-                // Deselect idx as it might be out of range
-                kunai.tasks.deselect_index();
-            }
+            // Deselect idx as it might be out of range
+            kunai.tasks.deselect_index();
         }
         KeyCode::Enter => match kunai.tasks.table_state.selected() {
             Some(idx) => {
@@ -83,18 +91,58 @@ fn handle_taskselection(kunai: &mut Kunai, key: KeyEvent) -> bool {
 }
 
 fn handle_memoryeditor(kunai: &mut Kunai, key: KeyEvent) -> bool {
+    // Only allow cancelling while a scan is running
+    if kunai.memedit.is_scanning() {
+        if key.code == KeyCode::Esc {
+            kunai.memedit.cancel_scan();
+        }
+        return true;
+    }
+
+    // Works everywhere, even while typing
+    if key.code == KeyCode::F(2) {
+        kunai.memedit.toggle_read_only();
+        return true;
+    }
+
+    if kunai.memedit.map_glob.is_some() {
+        return handle_map_glob(kunai, key);
+    }
+
+    if kunai.memedit.address_input.is_some() {
+        return handle_address_input(kunai, key);
+    }
+
+    if kunai.memedit.goto_input.is_some() {
+        return handle_goto_input(kunai, key);
+    }
+
+    if kunai.memedit.sub_screen == SubScreen::HexView {
+        return handle_hex_view(kunai, key);
+    }
+
     match key.code {
         KeyCode::Tab => {
             kunai.memedit.sub_screen = match kunai.memedit.sub_screen {
                 SubScreen::MemorySearch => SubScreen::MemoryMaps,
                 SubScreen::MemoryMaps => SubScreen::MemorySearch,
                 SubScreen::ValueEditing => SubScreen::MemorySearch,
+                SubScreen::PointerScan => SubScreen::MemorySearch,
+                SubScreen::AddressList => SubScreen::MemorySearch,
+                SubScreen::HexView => SubScreen::MemorySearch,
+                SubScreen::StructView => SubScreen::MemorySearch,
+                SubScreen::Journal => SubScreen::MemorySearch,
             }
         }
         KeyCode::Esc => match kunai.memedit.sub_screen {
             SubScreen::MemorySearch => kunai.current_screen = CurrentScreen::TaskSelectionScreen,
             SubScreen::MemoryMaps => kunai.memedit.sub_screen = SubScreen::MemorySearch,
             SubScreen::ValueEditing => kunai.memedit.sub_screen = SubScreen::MemorySearch,
+            SubScreen::PointerScan => kunai.memedit.sub_screen = SubScreen::MemorySearch,
+            SubScreen::AddressList
+            | SubScreen::HexView
+            | SubScreen::StructView
+            | SubScreen::Journal => kunai.memedit.sub_screen = SubScreen::MemorySearch,
         },
         KeyCode::Char(c) => match kunai.memedit.sub_screen {
             SubScreen::MemorySearch => {
@@ -103,6 +151,26 @@ fn handle_memoryeditor(kunai: &mut Kunai, key: KeyEvent) -> bool {
                         // TODO: Impl refresh search, with prev value
                         'r' => kunai.memedit.refresh_list(),
                         'm' => kunai.memedit.sub_screen = SubScreen::MemoryMaps,
+                        't' => kunai.memedit.cycle_value_type(),
+                        'b' => kunai.memedit.toggle_endianness(),
+                        'a' => kunai.memedit.cycle_alignment(),
+                        'n' => kunai.memedit.next_scan(),
+                        'u' => kunai.memedit.unknown_value_scan(),
+                        'x' => kunai.memedit.toggle_aob_executable_only(),
+                        'w' => kunai.memedit.cycle_encoding(),
+                        'y' => kunai.memedit.toggle_case_insensitive(),
+                        's' => kunai.memedit.cycle_compare_mode(),
+                        'o' => kunai.memedit.toggle_compare_against(),
+                        'p' => kunai.memedit.open_pointer_scan(),
+                        'f' => kunai.memedit.toggle_freeze(),
+                        'g' => kunai.memedit.cycle_freeze_mode(),
+                        'l' => kunai.memedit.cycle_freeze_interval(),
+                        'k' => kunai.memedit.add_to_address_list(),
+                        'd' => kunai.memedit.sub_screen = SubScreen::AddressList,
+                        'v' => kunai.memedit.goto_input = Some(String::new()),
+                        'q' => kunai.memedit.open_hex_view(),
+                        'z' => kunai.memedit.open_struct_view_at_result(),
+                        'j' => kunai.memedit.sub_screen = SubScreen::Journal,
                         'e' => {
                             // Check if value is highlihted for editing
                             let selected_loc = match kunai.memedit.search_table_state.selected() {
@@ -118,16 +186,80 @@ fn handle_memoryeditor(kunai: &mut Kunai, key: KeyEvent) -> bool {
                     kunai.memedit.search_string.push(c)
                 }
             }
-            SubScreen::MemoryMaps => {}
+            SubScreen::MemoryMaps => match c {
+                ' ' => kunai.memedit.toggle_selected_map(),
+                'a' => kunai.memedit.apply_map_preset(MapPreset::All),
+                'n' => kunai.memedit.apply_map_preset(MapPreset::None),
+                'w' => kunai
+                    .memedit
+                    .apply_map_preset(MapPreset::Perms("rw-p".to_string())),
+                'f' => kunai.memedit.apply_map_preset(MapPreset::ExcludeFileBacked),
+                'h' => kunai.memedit.apply_map_preset(MapPreset::HeapStackAnon),
+                'o' => kunai.memedit.select_module_maps(),
+                '/' => kunai.memedit.map_glob = Some(String::new()),
+                _ => {}
+            },
             SubScreen::ValueEditing => {
-                kunai.memedit.new_value.push(c);
+                if key.modifiers == KeyModifiers::CONTROL {
+                    match c {
+                        'f' => kunai.memedit.freeze_new_value(),
+                        'n' => kunai.memedit.cycle_str_fill(),
+                        _ => {}
+                    }
+                } else {
+                    kunai.memedit.new_value.push(c);
+                }
             }
+            SubScreen::PointerScan => match c {
+                'd' => kunai.memedit.cycle_pointer_depth(),
+                'o' => kunai.memedit.cycle_pointer_offset(),
+                'r' => kunai.memedit.resolve_pointer_chains(),
+                's' => kunai.memedit.save_pointer_map(),
+                'c' => kunai.memedit.compare_pointer_map(),
+                'a' => kunai.memedit.add_chain_to_address_list(),
+                _ => {}
+            },
+            SubScreen::AddressList => match c {
+                'x' => kunai.memedit.remove_address(),
+                'h' => kunai.memedit.open_hex_view_at_address(),
+                'f' => kunai.memedit.toggle_address_freeze(),
+                'g' => kunai.memedit.toggle_group_freeze(),
+                'd' => kunai.memedit.start_address_description(),
+                's' => kunai.memedit.save_address_table(),
+                'l' => kunai.memedit.load_address_table(),
+                '0' => kunai.memedit.set_address_group(None),
+                '1'..='9' => kunai.memedit.set_address_group(Some(c)),
+                _ => {}
+            },
+            SubScreen::HexView => {}
+            SubScreen::StructView => match c {
+                ' ' => kunai.memedit.struct_view.toggle_selected(),
+                't' => kunai.memedit.cycle_struct_def(),
+                'r' => kunai.memedit.reload_structs(),
+                'a' => kunai.memedit.auto_dissect(),
+                'y' => kunai.memedit.cycle_struct_field_type(),
+                's' => kunai.memedit.save_struct_draft(),
+                'h' => kunai.memedit.struct_field_hex_view(),
+                'g' => kunai.memedit.goto_input = Some(String::new()),
+                _ => {}
+            },
+            SubScreen::Journal => match c {
+                'u' => kunai.memedit.undo_writes(1),
+                'r' => kunai.memedit.redo_writes(1),
+                'a' => kunai.memedit.undo_all_writes(),
+                _ => {}
+            },
         },
         KeyCode::Backspace => match kunai.memedit.sub_screen {
             SubScreen::MemorySearch => {
                 kunai.memedit.search_string.pop();
             }
-            SubScreen::MemoryMaps => {}
+            SubScreen::MemoryMaps
+            | SubScreen::PointerScan
+            | SubScreen::AddressList
+            | SubScreen::HexView
+            | SubScreen::StructView
+            | SubScreen::Journal => {}
             SubScreen::ValueEditing => {
                 kunai.memedit.new_value.pop();
             }
@@ -138,13 +270,16 @@ fn handle_memoryeditor(kunai: &mut Kunai, key: KeyEvent) -> bool {
                 kunai.memedit.search_table_state.select(None);
                 kunai.memedit.search_memory();
             }
-            SubScreen::MemoryMaps => {
-                // TODO: Enable memory search toggle
-            }
+            SubScreen::MemoryMaps => kunai.memedit.toggle_selected_map(),
             SubScreen::ValueEditing => {
                 // Edit the memory
                 kunai.memedit.edit_memory();
             }
+            SubScreen::PointerScan => kunai.memedit.pointer_scan(),
+            SubScreen::AddressList => kunai.memedit.edit_address(),
+            SubScreen::HexView => {}
+            SubScreen::StructView => kunai.memedit.select_struct_field(),
+            SubScreen::Journal => kunai.memedit.rewind_journal(),
         },
         KeyCode::Up => match kunai.memedit.sub_screen {
             SubScreen::MemoryMaps => {
@@ -165,7 +300,54 @@ fn handle_memoryeditor(kunai: &mut Kunai, key: KeyEvent) -> bool {
                 }
                 kunai.memedit.search_table_state.select(Some(curr_idx));
             }
-            SubScreen::ValueEditing => {}
+            SubScreen::PointerScan => {
+                let len = kunai.memedit.pointer_chains.len();
+                if len > 0 {
+                    let curr_idx = kunai.memedit.pointer_table_state.selected().unwrap_or(0);
+                    kunai
+                        .memedit
+                        .pointer_table_state
+                        .select(Some((curr_idx + len - 1) % len));
+                }
+            }
+            SubScreen::StructView => {
+                let len = kunai.memedit.struct_view.rows.len();
+                if len > 0 {
+                    let curr_idx = kunai
+                        .memedit
+                        .struct_view
+                        .table_state
+                        .selected()
+                        .unwrap_or(0);
+                    kunai
+                        .memedit
+                        .struct_view
+                        .table_state
+                        .select(Some((curr_idx + len - 1) % len));
+                }
+            }
+            SubScreen::AddressList => {
+                let len = kunai.memedit.address_list.len();
+                if len > 0 {
+                    let curr_idx = kunai.memedit.address_table_state.selected().unwrap_or(0);
+                    kunai
+                        .memedit
+                        .address_table_state
+                        .select(Some((curr_idx + len - 1) % len));
+                }
+            }
+            SubScreen::Journal => {
+                let len = kunai.memedit.journal.rows().count();
+                if len > 0 {
+                    let curr_idx = kunai.memedit.journal.table_state.selected().unwrap_or(0);
+                    kunai
+                        .memedit
+                        .journal
+                        .table_state
+                        .select(Some((curr_idx + len - 1) % len));
+                }
+            }
+            SubScreen::ValueEditing | SubScreen::HexView => {}
         },
         KeyCode::Down => match kunai.memedit.sub_screen {
             SubScreen::MemoryMaps => {
@@ -186,10 +368,140 @@ fn handle_memoryeditor(kunai: &mut Kunai, key: KeyEvent) -> bool {
                 }
                 kunai.memedit.search_table_state.select(Some(curr_idx));
             }
-            SubScreen::ValueEditing => {}
+            SubScreen::PointerScan => {
+                let len = kunai.memedit.pointer_chains.len();
+                if len > 0 {
+                    let curr_idx = match kunai.memedit.pointer_table_state.selected() {
+                        Some(i) => (i + 1) % len,
+                        None => 0,
+                    };
+                    kunai.memedit.pointer_table_state.select(Some(curr_idx));
+                }
+            }
+            SubScreen::StructView => {
+                let len = kunai.memedit.struct_view.rows.len();
+                if len > 0 {
+                    let curr_idx = match kunai.memedit.struct_view.table_state.selected() {
+                        Some(i) => (i + 1) % len,
+                        None => 0,
+                    };
+                    kunai.memedit.struct_view.table_state.select(Some(curr_idx));
+                }
+            }
+            SubScreen::AddressList => {
+                let len = kunai.memedit.address_list.len();
+                if len > 0 {
+                    let curr_idx = match kunai.memedit.address_table_state.selected() {
+                        Some(i) => (i + 1) % len,
+                        None => 0,
+                    };
+                    kunai.memedit.address_table_state.select(Some(curr_idx));
+                }
+            }
+            SubScreen::Journal => {
+                let len = kunai.memedit.journal.rows().count();
+                if len > 0 {
+                    let curr_idx = match kunai.memedit.journal.table_state.selected() {
+                        Some(i) => (i + 1) % len,
+                        None => 0,
+                    };
+                    kunai.memedit.journal.table_state.select(Some(curr_idx));
+                }
+            }
+            SubScreen::ValueEditing | SubScreen::HexView => {}
         },
         _ => {}
     }
 
     true
 }
+
+/// Typing a name glob in the maps popup
+fn handle_map_glob(kunai: &mut Kunai, key: KeyEvent) -> bool {
+    let glob = match &mut kunai.memedit.map_glob {
+        Some(g) => g,
+        None => return true,
+    };
+
+    match key.code {
+        KeyCode::Char(c) => glob.push(c),
+        KeyCode::Backspace => {
+            glob.pop();
+        }
+        KeyCode::Enter => kunai.memedit.apply_map_glob(),
+        KeyCode::Esc => kunai.memedit.map_glob = None,
+        _ => {}
+    }
+
+    true
+}
+
+/// Typing the description of an address list entry
+fn handle_address_input(kunai: &mut Kunai, key: KeyEvent) -> bool {
+    let input = match &mut kunai.memedit.address_input {
+        Some(i) => i,
+        None => return true,
+    };
+
+    match key.code {
+        KeyCode::Char(c) => input.push(c),
+        KeyCode::Backspace => {
+            input.pop();
+        }
+        KeyCode::Enter => kunai.memedit.apply_address_description(),
+        KeyCode::Esc => kunai.memedit.address_input = None,
+        _ => {}
+    }
+
+    true
+}
+
+/// Typing an address to go to in the search screen
+fn handle_goto_input(kunai: &mut Kunai, key: KeyEvent) -> bool {
+    let input = match &mut kunai.memedit.goto_input {
+        Some(i) => i,
+        None => return true,
+    };
+
+    match key.code {
+        KeyCode::Char(c) => input.push(c),
+        KeyCode::Backspace => {
+            input.pop();
+        }
+        KeyCode::Enter => kunai.memedit.goto_address(),
+        KeyCode::Esc => kunai.memedit.goto_input = None,
+        _ => {}
+    }
+
+    true
+}
+
+/// Moving around and typing over bytes in the hex view
+fn handle_hex_view(kunai: &mut Kunai, key: KeyEvent) -> bool {
+    let view = &mut kunai.memedit.hex_view;
+
+    match key.code {
+        KeyCode::Left => view.move_cursor(-1),
+        KeyCode::Right => view.move_cursor(1),
+        KeyCode::Up => view.move_cursor(-(ROW_BYTES as isize)),
+        KeyCode::Down => view.move_cursor(ROW_BYTES as isize),
+        KeyCode::PageUp => view.page(false),
+        KeyCode::PageDown => view.page(true),
+        KeyCode::Char('g') => kunai.memedit.goto_input = Some(String::new()),
+        KeyCode::Char('s') => kunai.memedit.open_struct_view_at_cursor(),
+        KeyCode::Char('u') => kunai.memedit.undo_writes(1),
+        KeyCode::Char('r') => kunai.memedit.redo_writes(1),
+        KeyCode::Char(c) => {
+            if let Some(digit) = c.to_digit(16) {
+                kunai.memedit.hex_edit(digit as u8);
+            }
+        }
+        // Drop a half typed byte first
+        KeyCode::Esc | KeyCode::Tab if view.nibble.take().is_none() => {
+            kunai.memedit.sub_screen = SubScreen::MemorySearch;
+        }
+        _ => {}
+    }
+
+    true
+}
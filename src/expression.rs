@@ -0,0 +1,319 @@
+use crate::{
+    address::format_hex,
+    memory_model::MemoryMap,
+    pointer_scan::{module_base, read_pointer},
+    symbols::find_symbol,
+};
+
+/// What an address expression is evaluated against
+#[derive(Debug, Clone, Copy)]
+pub struct EvalContext<'a> {
+    pub pid: &'a str,
+    pub maps: &'a [MemoryMap],
+    /// Pointer size of the process, how many bytes `[expr]` reads
+    pub width: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(usize),
+    /// A module or a symbol
+    Name(String),
+    Plus,
+    Minus,
+    Star,
+    Open(char),
+    Close(char),
+}
+
+/// Evaluates an address expression.
+///
+/// - `1A2B` and `0x1A2B` are hex, `#100` is decimal, hex starting with a letter needs the `0x`
+/// - `libgame.so` is where a module is loaded, `"ld-linux-x86-64.so.2"` for names with a `-`
+/// - `main` or `libc.so.6!malloc` is the address of a symbol
+/// - `[expr]` reads the pointer at `expr`
+/// - `+`, `-`, `*` and parentheses
+///
+/// e.g. `[[game+0x1000]+0x18]+0x4`
+pub fn evaluate(ctx: &EvalContext, input: &str) -> Result<usize, String> {
+    let tokens = tokenize(input)?;
+    if tokens.is_empty() {
+        return Err("Empty address".to_string());
+    }
+
+    let mut parser = Parser {
+        ctx,
+        tokens: &tokens,
+        pos: 0,
+    };
+    let value = parser.expr()?;
+
+    match parser.tokens.get(parser.pos) {
+        None => Ok(value),
+        Some(t) => Err(format!("Unexpected {} in '{}'", describe(t), input.trim())),
+    }
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = input.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        let token = match c {
+            ' ' | '\t' => continue,
+            '+' => Token::Plus,
+            '-' => Token::Minus,
+            '*' => Token::Star,
+            '[' | '(' => Token::Open(c),
+            ']' | ')' => Token::Close(c),
+            '#' => {
+                let digits = take_while(&mut chars, |c| c.is_ascii_digit());
+                let value = digits
+                    .parse()
+                    .map_err(|_| format!("'#{}' is not a decimal number", digits))?;
+                Token::Number(value)
+            }
+            '"' => {
+                let name = take_while(&mut chars, |c| c != '"');
+                if chars.next() != Some('"') {
+                    return Err(format!("Missing closing quote after \"{}", name));
+                }
+                Token::Name(name)
+            }
+            c if c.is_ascii_digit() => {
+                let word = c.to_string() + &take_while(&mut chars, is_name_char);
+                match word.strip_prefix("0x").or(word.strip_prefix("0X")) {
+                    Some(hex) => Token::Number(parse_radix(hex, &word)?),
+                    // Plain numbers are hex, like addresses are shown everywhere
+                    None if word.chars().all(|c| c.is_ascii_hexdigit()) => {
+                        Token::Number(parse_radix(&word, &word)?)
+                    }
+                    None => Token::Name(word),
+                }
+            }
+            c if is_name_char(c) => {
+                Token::Name(c.to_string() + &take_while(&mut chars, is_name_char))
+            }
+            c => return Err(format!("Unexpected '{}'", c)),
+        };
+        tokens.push(token);
+    }
+
+    Ok(tokens)
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '!' | '@' | '/')
+}
+
+/// Quotes module names `evaluate` wouldn't read as one name, like `ld-linux-x86-64.so.2`
+pub fn quote_name(name: &str) -> String {
+    match name.chars().all(is_name_char) {
+        true => name.to_string(),
+        false => format!("\"{}\"", name),
+    }
+}
+
+fn take_while(
+    chars: &mut std::iter::Peekable<std::str::Chars>,
+    keep: impl Fn(char) -> bool,
+) -> String {
+    let mut s = String::new();
+    while let Some(c) = chars.next_if(|c| keep(*c)) {
+        s.push(c);
+    }
+    s
+}
+
+fn parse_radix(digits: &str, word: &str) -> Result<usize, String> {
+    usize::from_str_radix(digits, 16).map_err(|_| format!("'{}' is not a hex number", word))
+}
+
+fn describe(token: &Token) -> String {
+    match token {
+        Token::Number(n) => format!("'{}'", format_hex(*n)),
+        Token::Name(n) => format!("'{}'", n),
+        Token::Plus => "'+'".to_string(),
+        Token::Minus => "'-'".to_string(),
+        Token::Star => "'*'".to_string(),
+        Token::Open(c) | Token::Close(c) => format!("'{}'", c),
+    }
+}
+
+/// Recursive descent, `*` binds tighter than `+` and `-`
+struct Parser<'a> {
+    ctx: &'a EvalContext<'a>,
+    tokens: &'a [Token],
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn next(&mut self) -> Option<&Token> {
+        let token = self.tokens.get(self.pos);
+        self.pos += 1;
+        token
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn expr(&mut self) -> Result<usize, String> {
+        let mut value = self.term()?;
+        loop {
+            match self.peek() {
+                Some(Token::Plus) => {
+                    self.pos += 1;
+                    value = value.wrapping_add(self.term()?);
+                }
+                Some(Token::Minus) => {
+                    self.pos += 1;
+                    value = value.wrapping_sub(self.term()?);
+                }
+                _ => return Ok(value),
+            }
+        }
+    }
+
+    fn term(&mut self) -> Result<usize, String> {
+        let mut value = self.factor()?;
+        while self.peek() == Some(&Token::Star) {
+            self.pos += 1;
+            value = value.wrapping_mul(self.factor()?);
+        }
+        Ok(value)
+    }
+
+    fn factor(&mut self) -> Result<usize, String> {
+        let token = match self.next() {
+            Some(t) => t.clone(),
+            None => return Err("Address ends too early".to_string()),
+        };
+
+        match token {
+            Token::Number(n) => Ok(n),
+            Token::Name(name) => self.name(&name),
+            Token::Minus => Ok(self.factor()?.wrapping_neg()),
+            Token::Open(open) => {
+                let value = self.expr()?;
+                let close = match open {
+                    '[' => ']',
+                    _ => ')',
+                };
+                if self.next() != Some(&Token::Close(close)) {
+                    return Err(format!("Missing '{}'", close));
+                }
+
+                match open {
+                    '[' => read_pointer(self.ctx.pid, value, self.ctx.width)
+                        .ok_or_else(|| format!("Can't read a pointer at {}", format_hex(value))),
+                    _ => Ok(value),
+                }
+            }
+            t => Err(format!("Unexpected {}", describe(&t))),
+        }
+    }
+
+    /// A module, then a symbol
+    fn name(&self, name: &str) -> Result<usize, String> {
+        if let Some(base) = module_base(self.ctx.maps, name) {
+            return Ok(base);
+        }
+        if let Some(addr) = find_symbol(self.ctx.pid, self.ctx.maps, name) {
+            return Ok(addr);
+        }
+        match name.chars().all(|c| c.is_ascii_hexdigit()) {
+            true => Err(format!(
+                "'{}' is not a loaded module or a symbol, write 0x{} for the number",
+                name, name
+            )),
+            false => Err(format!("'{}' is not a loaded module or a symbol", name)),
+        }
+    }
+}
+
+/// Rewrites addresses saved before expressions existed so `evaluate` reads them the same:
+/// bare hex like `deadbeef` gets its `0x`, and modules like `libstdc++.so.6+0x10` get quoted
+pub fn migrate_address(address: &str) -> String {
+    let address = address.trim();
+    if !address.is_empty() && address.chars().all(|c| c.is_ascii_hexdigit()) {
+        return format!("0x{}", address);
+    }
+
+    match address.rsplit_once("+0x") {
+        Some((module, offset))
+            if !module.is_empty()
+                && !module.starts_with('"')
+                && module
+                    .chars()
+                    .all(|c| !c.is_whitespace() && !"[]()\"".contains(c))
+                && offset.chars().all(|c| c.is_ascii_hexdigit()) =>
+        {
+            format!("{}+0x{}", quote_name(module), offset)
+        }
+        _ => address.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(input: &str) -> Result<usize, String> {
+        let pid = std::process::id().to_string();
+        let ctx = EvalContext {
+            pid: &pid,
+            maps: &[],
+            width: std::mem::size_of::<usize>(),
+        };
+        evaluate(&ctx, input)
+    }
+
+    #[test]
+    fn numbers() {
+        assert_eq!(eval("10"), Ok(0x10));
+        assert_eq!(eval("1A2B"), Ok(0x1A2B));
+        assert_eq!(eval("0xdead"), Ok(0xDEAD));
+        assert_eq!(eval("#100"), Ok(100));
+    }
+
+    #[test]
+    fn precedence() {
+        assert_eq!(eval("2+3*4"), Ok(0xE));
+        assert_eq!(eval("(2+3)*4"), Ok(0x14));
+        assert_eq!(eval("10-2-1"), Ok(0xD));
+        assert_eq!(eval("-1+2"), Ok(1));
+    }
+
+    #[test]
+    fn deref() {
+        let target = Box::new(0x1234usize);
+        let slot = Box::new(&*target as *const usize as usize);
+        let addr = &*slot as *const usize as usize;
+
+        assert_eq!(eval(&format!("[0x{:X}]", addr)), Ok(*slot));
+        assert_eq!(eval(&format!("[[0x{:X}]]+#1", addr)), Ok(0x1235));
+        assert!(eval("[0]").is_err());
+        assert!(eval(&format!("[0x{:X}", addr)).is_err());
+    }
+
+    #[test]
+    fn unknown_names() {
+        assert!(eval("no_such_module.so+0x10").is_err());
+        // Hex starting with a letter needs its 0x
+        assert!(eval("dead").is_err());
+        assert!(eval("1+").is_err());
+    }
+
+    #[test]
+    fn old_addresses() {
+        assert_eq!(migrate_address("7ffc1000"), "0x7ffc1000");
+        assert_eq!(migrate_address("deadbeef"), "0xdeadbeef");
+        assert_eq!(
+            migrate_address("libstdc++.so.6+0x10"),
+            "\"libstdc++.so.6\"+0x10"
+        );
+        assert_eq!(migrate_address("game+0x10"), "game+0x10");
+        assert_eq!(migrate_address("[game+0x10]+0x4"), "[game+0x10]+0x4");
+    }
+}
@@ -0,0 +1,325 @@
+use std::{
+    fmt,
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+use crate::{
+    proc_mem::{is_read_only, read_batch, write_mem},
+    trace_dbg,
+    value_type::{Endianness, ValueType},
+};
+
+/// Intervals `cycle_interval` goes through, in milliseconds
+const INTERVALS: [u64; 5] = [10, 50, 100, 500, 1000];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FreezeMode {
+    /// Always rewrite the value
+    Exact,
+    /// Only rewrite once the value drops below it
+    NeverBelow,
+    /// Only rewrite once the value goes above it
+    NeverAbove,
+}
+
+impl FreezeMode {
+    pub fn next(self) -> FreezeMode {
+        match self {
+            FreezeMode::Exact => FreezeMode::NeverBelow,
+            FreezeMode::NeverBelow => FreezeMode::NeverAbove,
+            FreezeMode::NeverAbove => FreezeMode::Exact,
+        }
+    }
+
+    /// Parses the `Display` form, e.g. `never below`
+    pub fn from_name(name: &str) -> Result<FreezeMode, String> {
+        let mut mode = FreezeMode::Exact;
+        loop {
+            if mode.to_string() == name {
+                return Ok(mode);
+            }
+            mode = mode.next();
+            if mode == FreezeMode::Exact {
+                return Err(format!("Unknown freeze mode '{}'", name));
+            }
+        }
+    }
+
+    /// Short form shown next to frozen values
+    pub fn symbol(self) -> &'static str {
+        match self {
+            FreezeMode::Exact => "=",
+            FreezeMode::NeverBelow => ">=",
+            FreezeMode::NeverAbove => "<=",
+        }
+    }
+}
+
+impl fmt::Display for FreezeMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            FreezeMode::Exact => "exact",
+            FreezeMode::NeverBelow => "never below",
+            FreezeMode::NeverAbove => "never above",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// A value kept in place by the freezer
+#[derive(Debug, Clone)]
+pub struct Freeze {
+    pub addr: usize,
+    pub value_type: ValueType,
+    pub endianness: Endianness,
+    /// The encoded value that gets written
+    pub bytes: Vec<u8>,
+    pub mode: FreezeMode,
+}
+
+impl Freeze {
+    pub fn new(
+        addr: usize,
+        value_type: ValueType,
+        endianness: Endianness,
+        bytes: Vec<u8>,
+        mode: FreezeMode,
+    ) -> Result<Freeze, String> {
+        if mode != FreezeMode::Exact && !value_type.is_numeric() {
+            return Err(format!("Only numbers can be frozen as '{}'", mode));
+        }
+
+        Ok(Freeze {
+            addr,
+            value_type,
+            endianness,
+            bytes,
+            mode,
+        })
+    }
+
+    /// e.g. `>= 999`
+    pub fn label(&self) -> String {
+        format!(
+            "{} {}",
+            self.mode.symbol(),
+            self.value_type.decode(&self.bytes, self.endianness)
+        )
+    }
+
+    /// Whether the current value has to be overwritten
+    fn needs_write(&self, current: &[u8]) -> bool {
+        let (current, frozen) = match (
+            self.value_type.number(current, self.endianness),
+            self.value_type.number(&self.bytes, self.endianness),
+        ) {
+            (Some(c), Some(f)) => (c, f),
+            _ => return current != self.bytes,
+        };
+
+        match self.mode {
+            FreezeMode::Exact => current != frozen,
+            FreezeMode::NeverBelow => current < frozen,
+            FreezeMode::NeverAbove => current > frozen,
+        }
+    }
+}
+
+/// How often a frozen value was rewritten since the rewrites were last taken
+#[derive(Debug, Clone)]
+pub struct Rewrites {
+    pub addr: usize,
+    pub count: u64,
+    /// What the last rewrite overwrote
+    pub old: Vec<u8>,
+    pub new: Vec<u8>,
+}
+
+/// Keeps rewriting frozen values of a process on a background thread.
+/// The thread stops when the freezer is dropped or the process exits.
+#[derive(Debug)]
+pub struct Freezer {
+    freezes: Arc<Mutex<Vec<Freeze>>>,
+    rewrites: Arc<Mutex<Vec<Rewrites>>>,
+    interval_ms: Arc<AtomicU64>,
+    stop: Arc<AtomicBool>,
+    exited: Arc<AtomicBool>,
+}
+
+impl Freezer {
+    pub fn start(pid: &str) -> Freezer {
+        let freezer = Freezer {
+            freezes: Arc::new(Mutex::new(Vec::new())),
+            rewrites: Arc::new(Mutex::new(Vec::new())),
+            interval_ms: Arc::new(AtomicU64::new(INTERVALS[2])),
+            stop: Arc::new(AtomicBool::new(false)),
+            exited: Arc::new(AtomicBool::new(false)),
+        };
+
+        let pid = pid.to_string();
+        let freezes = freezer.freezes.clone();
+        let rewrites = freezer.rewrites.clone();
+        let interval_ms = freezer.interval_ms.clone();
+        let stop = freezer.stop.clone();
+        let exited = freezer.exited.clone();
+
+        thread::spawn(move || {
+            while !stop.load(Ordering::Relaxed) {
+                if !Path::new(&format!("/proc/{}", pid)).exists() {
+                    exited.store(true, Ordering::Relaxed);
+                    break;
+                }
+
+                if is_read_only() {
+                    thread::sleep(Duration::from_millis(interval_ms.load(Ordering::Relaxed)));
+                    continue;
+                }
+
+                // Work on a copy, so toggling freezes never waits on the target
+                let current: Vec<Freeze> = match freezes.lock() {
+                    Ok(f) => f.clone(),
+                    Err(_) => break,
+                };
+                let written = write_frozen(&pid, &current);
+                if let Ok(mut rewrites) = rewrites.lock() {
+                    add_rewrites(&mut rewrites, written);
+                }
+
+                thread::sleep(Duration::from_millis(interval_ms.load(Ordering::Relaxed)));
+            }
+        });
+
+        freezer
+    }
+
+    /// Freezes the address, or unfreezes it if it already was. Returns whether it's frozen now.
+    pub fn toggle(&self, freeze: Freeze) -> bool {
+        let mut freezes = match self.freezes.lock() {
+            Ok(f) => f,
+            Err(_) => return false,
+        };
+
+        match freezes.iter().position(|f| f.addr == freeze.addr) {
+            Some(idx) => {
+                freezes.remove(idx);
+                false
+            }
+            None => {
+                freezes.push(freeze);
+                true
+            }
+        }
+    }
+
+    /// Freezes the address, replacing an older freeze of it
+    pub fn insert(&self, freeze: Freeze) {
+        if let Ok(mut freezes) = self.freezes.lock() {
+            freezes.retain(|f| f.addr != freeze.addr);
+            freezes.push(freeze);
+        }
+    }
+
+    pub fn remove(&self, addr: usize) {
+        if let Ok(mut freezes) = self.freezes.lock() {
+            freezes.retain(|f| f.addr != addr);
+        }
+    }
+
+    pub fn get(&self, addr: usize) -> Option<Freeze> {
+        self.freezes
+            .lock()
+            .ok()?
+            .iter()
+            .find(|f| f.addr == addr)
+            .cloned()
+    }
+
+    pub fn count(&self) -> usize {
+        self.freezes.lock().map_or(0, |f| f.len())
+    }
+
+    pub fn interval(&self) -> u64 {
+        self.interval_ms.load(Ordering::Relaxed)
+    }
+
+    pub fn cycle_interval(&self) {
+        let idx = INTERVALS
+            .iter()
+            .position(|i| *i == self.interval())
+            .unwrap_or(0);
+        self.interval_ms
+            .store(INTERVALS[(idx + 1) % INTERVALS.len()], Ordering::Relaxed);
+    }
+
+    /// The rewrites made since this was last called
+    pub fn take_rewrites(&self) -> Vec<Rewrites> {
+        self.rewrites
+            .lock()
+            .map_or(Vec::new(), |mut r| std::mem::take(&mut *r))
+    }
+
+    /// Set once the writer thread noticed the process is gone
+    pub fn has_exited(&self) -> bool {
+        self.exited.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for Freezer {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Reads every frozen value in one go and rewrites the ones that drifted,
+/// returning the rewritten freezes with what they overwrote
+fn write_frozen(pid: &str, freezes: &[Freeze]) -> Vec<Rewrites> {
+    let ranges: Vec<(usize, usize)> = freezes
+        .iter()
+        .map(|f| (f.addr, f.addr + f.bytes.len()))
+        .collect();
+
+    let mut written = Vec::new();
+    for (freeze, current) in freezes.iter().zip(read_batch(pid, &ranges)) {
+        let current = match current {
+            Ok(c) if freeze.needs_write(&c) => c,
+            Ok(_) => continue,
+            Err(e) => {
+                trace_dbg!(e);
+                continue;
+            }
+        };
+
+        match write_mem(pid, freeze.addr, &freeze.bytes) {
+            Ok(_) => written.push(Rewrites {
+                addr: freeze.addr,
+                count: 1,
+                old: current,
+                new: freeze.bytes.clone(),
+            }),
+            Err(e) => {
+                trace_dbg!(e);
+            }
+        }
+    }
+
+    written
+}
+
+fn add_rewrites(rewrites: &mut Vec<Rewrites>, written: Vec<Rewrites>) {
+    for w in written {
+        match rewrites.iter_mut().find(|r| r.addr == w.addr) {
+            Some(r) => {
+                r.count += w.count;
+                r.old = w.old;
+                r.new = w.new;
+            }
+            None => rewrites.push(w),
+        }
+    }
+}
@@ -0,0 +1,99 @@
+use crate::proc_mem::read_pages;
+
+/// Bytes per row of the dump
+pub const ROW_BYTES: usize = 16;
+
+/// Rows kept above the cursor when jumping to an address
+const CONTEXT_ROWS: usize = 4;
+
+/// A window of memory shown as offset / hex / ASCII rows
+#[derive(Debug)]
+pub struct HexView {
+    /// First address shown, a multiple of `ROW_BYTES`
+    pub top: usize,
+    pub cursor: usize,
+    /// Rows that fit on screen, updated while rendering
+    pub rows: usize,
+    /// Bytes from `top` on, `None` where they couldn't be read
+    pub bytes: Vec<Option<u8>>,
+    /// First digit typed for the byte under the cursor
+    pub nibble: Option<u8>,
+}
+
+impl HexView {
+    pub fn new() -> HexView {
+        HexView {
+            top: 0,
+            cursor: 0,
+            rows: 16,
+            bytes: Vec::new(),
+            nibble: None,
+        }
+    }
+
+    /// Moves the cursor to `addr`, with a few rows before it in view
+    pub fn jump(&mut self, addr: usize) {
+        self.cursor = addr;
+        self.top = row_start(addr).saturating_sub(CONTEXT_ROWS * ROW_BYTES);
+        self.nibble = None;
+        self.clamp();
+    }
+
+    /// Reads everything in view again
+    pub fn refresh(&mut self, pid: &str) {
+        self.clamp();
+        self.bytes = read_pages(pid, self.top, self.view());
+    }
+
+    pub fn byte_at(&self, addr: usize) -> Option<u8> {
+        *self.bytes.get(addr.checked_sub(self.top)?)?
+    }
+
+    /// Moves the cursor by `delta` bytes, scrolling to keep it in view
+    pub fn move_cursor(&mut self, delta: isize) {
+        self.cursor = self.cursor.saturating_add_signed(delta);
+        self.nibble = None;
+
+        let view = self.view();
+        if self.cursor < self.top {
+            self.top = row_start(self.cursor);
+        } else if self.cursor >= self.top.saturating_add(view) {
+            self.top = row_start(self.cursor) - (view - ROW_BYTES);
+        }
+        self.clamp();
+    }
+
+    /// Moves a whole screen up or down
+    pub fn page(&mut self, down: bool) {
+        let view = self.view() as isize;
+        self.move_cursor(if down { view } else { -view });
+    }
+
+    /// Bytes that fit on screen
+    fn view(&self) -> usize {
+        self.rows.max(1) * ROW_BYTES
+    }
+
+    /// Keeps the whole view, and the cursor, below the top of the address space
+    fn clamp(&mut self) {
+        let view = self.view();
+        let last_top = row_start(usize::MAX - view);
+        self.top = self.top.min(last_top);
+        self.cursor = self.cursor.min(last_top + view - 1);
+    }
+
+    /// Takes a typed hex digit, returning the new byte once both digits are in
+    pub fn type_digit(&mut self, digit: u8) -> Option<u8> {
+        match self.nibble.take() {
+            Some(high) => Some(high << 4 | digit),
+            None => {
+                self.nibble = Some(digit);
+                None
+            }
+        }
+    }
+}
+
+fn row_start(addr: usize) -> usize {
+    addr - addr % ROW_BYTES
+}
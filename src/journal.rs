@@ -0,0 +1,195 @@
+use ratatui::widgets::TableState;
+
+use crate::{
+    address::format_hex,
+    proc_mem::{read_batch, write_mem},
+};
+
+/// A write made from kunai, with the bytes it replaced
+#[derive(Debug, Clone)]
+pub struct JournalEntry {
+    pub addr: usize,
+    pub old: Vec<u8>,
+    pub new: Vec<u8>,
+}
+
+/// Writes that can be undone, and undone writes that can be redone
+#[derive(Debug)]
+pub struct WriteJournal {
+    /// Writes in place, oldest first
+    pub done: Vec<JournalEntry>,
+    /// Undone writes, the next to redo last
+    pub undone: Vec<JournalEntry>,
+    pub table_state: TableState,
+}
+
+impl WriteJournal {
+    pub fn new() -> WriteJournal {
+        WriteJournal {
+            done: Vec::new(),
+            undone: Vec::new(),
+            table_state: TableState::new(),
+        }
+    }
+
+    /// Writes `bytes` at `addr`, remembering what was there.
+    ///
+    /// Refuses to write what couldn't be undone, when the old bytes can't be read.
+    pub fn write(&mut self, pid: &str, addr: usize, bytes: &[u8]) -> Result<JournalEntry, String> {
+        let old = match read_batch(pid, &[(addr, addr + bytes.len())]).pop() {
+            Some(Ok(b)) => b,
+            _ => {
+                return Err(format!(
+                    "Can't read the bytes at {} to keep them",
+                    format_hex(addr)
+                ))
+            }
+        };
+
+        write_mem(pid, addr, bytes)
+            .map_err(|e| format!("Failed to write at {}: {}", format_hex(addr), e))?;
+
+        let entry = JournalEntry {
+            addr,
+            old,
+            new: bytes.to_vec(),
+        };
+        self.done.push(entry.clone());
+        self.undone.clear();
+        Ok(entry)
+    }
+
+    /// Puts back the bytes the last write replaced
+    pub fn undo(&mut self, pid: &str) -> Result<JournalEntry, String> {
+        let entry = self.done.pop().ok_or("Nothing to undo")?;
+        if let Err(e) = write_mem(pid, entry.addr, &entry.old) {
+            self.done.push(entry.clone());
+            return Err(format!(
+                "Failed to undo the write at {}: {}",
+                format_hex(entry.addr),
+                e
+            ));
+        }
+
+        self.undone.push(entry.clone());
+        Ok(entry)
+    }
+
+    /// Writes the last undone write again
+    pub fn redo(&mut self, pid: &str) -> Result<JournalEntry, String> {
+        let entry = self.undone.pop().ok_or("Nothing to redo")?;
+        if let Err(e) = write_mem(pid, entry.addr, &entry.new) {
+            self.undone.push(entry.clone());
+            return Err(format!(
+                "Failed to redo the write at {}: {}",
+                format_hex(entry.addr),
+                e
+            ));
+        }
+
+        self.done.push(entry.clone());
+        Ok(entry)
+    }
+
+    /// All writes newest first, undone ones on top, and whether each is still in place
+    pub fn rows(&self) -> impl Iterator<Item = (&JournalEntry, bool)> {
+        let undone = self.undone.iter().map(|e| (e, false));
+        let done = self.done.iter().rev().map(|e| (e, true));
+        undone.chain(done)
+    }
+
+    /// What going back to row `idx` of `rows` takes, `(count, undo)`:
+    /// undoing the write there and the ones after it, or redoing up to it if it's undone
+    pub fn rewind_to(&self, idx: usize) -> (usize, bool) {
+        let undone = self.undone.len();
+        match idx < undone {
+            true => (undone - idx, false),
+            false => (idx - undone + 1, true),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writes go to a buffer of the test process itself.
+    /// No reference to it is kept, the compiler doesn't know about those writes.
+    fn journal_on() -> (WriteJournal, String, *mut [u8; 4]) {
+        let pid = std::process::id().to_string();
+        (WriteJournal::new(), pid, Box::into_raw(Box::new([0u8; 4])))
+    }
+
+    fn bytes_at(buf: *mut [u8; 4]) -> [u8; 4] {
+        unsafe { std::ptr::read_volatile(buf) }
+    }
+
+    fn free(buf: *mut [u8; 4]) {
+        drop(unsafe { Box::from_raw(buf) });
+    }
+
+    fn addrs(journal: &WriteJournal) -> Vec<(usize, bool)> {
+        journal.rows().map(|(e, done)| (e.addr, done)).collect()
+    }
+
+    #[test]
+    fn undo_and_redo_in_order() {
+        let (mut journal, pid, buf) = journal_on();
+        let addr = buf as usize;
+
+        for i in 0..3 {
+            journal.write(&pid, addr + i, &[i as u8 + 1]).unwrap();
+        }
+        assert_eq!(bytes_at(buf), [1, 2, 3, 0]);
+        assert_eq!(
+            addrs(&journal),
+            [(addr + 2, true), (addr + 1, true), (addr, true)]
+        );
+
+        assert_eq!(journal.undo(&pid).unwrap().addr, addr + 2);
+        assert_eq!(journal.undo(&pid).unwrap().addr, addr + 1);
+        assert_eq!(bytes_at(buf), [1, 0, 0, 0]);
+        assert_eq!(
+            addrs(&journal),
+            [(addr + 2, false), (addr + 1, false), (addr, true)]
+        );
+
+        assert_eq!(journal.redo(&pid).unwrap().addr, addr + 1);
+        assert_eq!(bytes_at(buf), [1, 2, 0, 0]);
+
+        // A new write drops what's left to redo
+        journal.write(&pid, addr + 3, &[4]).unwrap();
+        assert!(journal.redo(&pid).is_err());
+        assert_eq!(
+            addrs(&journal),
+            [(addr + 3, true), (addr + 1, true), (addr, true)]
+        );
+        free(buf);
+    }
+
+    #[test]
+    fn nothing_to_undo() {
+        let (mut journal, pid, buf) = journal_on();
+        assert!(journal.undo(&pid).is_err());
+        assert!(journal.redo(&pid).is_err());
+        free(buf);
+    }
+
+    #[test]
+    fn rewind_counts() {
+        let entry = |addr| JournalEntry {
+            addr,
+            old: vec![0],
+            new: vec![1],
+        };
+        let mut journal = WriteJournal::new();
+        // Rows: 4, 3 undone, then 2, 1, 0 in place
+        journal.done = vec![entry(0), entry(1), entry(2)];
+        journal.undone = vec![entry(4), entry(3)];
+
+        assert_eq!(journal.rewind_to(0), (2, false));
+        assert_eq!(journal.rewind_to(1), (1, false));
+        assert_eq!(journal.rewind_to(2), (1, true));
+        assert_eq!(journal.rewind_to(4), (3, true));
+    }
+}
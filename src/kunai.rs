@@ -1,20 +1,36 @@
-use std::{
-    fs::File,
-    io::{Seek, SeekFrom, Write},
-    os::unix::{fs::FileExt, thread},
-    usize,
-};
+use std::time::{Duration, Instant};
 
 use ratatui::widgets::TableState;
 
 use crate::{
-    memory_model::{read_mem, search_mem, SearchLocation, TaskMemory},
-    proc_utils::get_tasks,
+    address::{find_map, format_address, format_hex},
+    address_list::{load_table, save_table, table_path, AddressEntry},
+    audit::{append_entry, current_uids, now, AuditEntry},
+    expression::{evaluate, EvalContext},
+    freezer::{Freeze, FreezeMode, Freezer},
+    hex_view::HexView,
+    journal::WriteJournal,
+    memory_model::{MapPreset, SearchLocation, SearchQuery, TaskMemory},
+    next_scan::{CompareAgainst, CompareMode, NextScan},
+    pointer_scan::{load_chains, pointer_map_path, save_chains, PointerChain, PointerScanConfig},
+    proc_mem::{check_writable, is_read_only, read_batch, toggle_read_only},
+    proc_utils::{get_tasks, pointer_width},
+    scan_pool::default_threads,
+    scan_worker::{ScanJob, ScanOutcome, ScanRequest},
+    snapshot::Snapshot,
+    struct_guess::{guess_layout, GUESS_SIZE},
+    structs::{
+        append_struct, load_structs, structs_path, unique_name, FieldKind, StructDef, StructView,
+    },
     trace_dbg,
     ui::{CurrentScreen, SubScreen},
-    utils::bytes_to_string,
+    utils::bytes_to_hex,
+    value_type::{Endianness, StrEncoding, StrFill, ValueType},
 };
 
+/// How often rewrites of frozen values are summed up in the audit log
+const FREEZE_AUDIT_INTERVAL: Duration = Duration::from_secs(10);
+
 #[derive(Debug)]
 pub struct Task {
     pub pid: String,
@@ -43,16 +59,66 @@ pub struct MemoryEditor {
     pub task_mem: TaskMemory,
     pub search_string: String,
     pub search_list: Vec<SearchLocation>,
+    /// Set while an unknown initial value scan has too many candidates to list
+    pub snapshot: Option<Snapshot>,
+    pub scan_job: Option<ScanJob>,
+
+    // Scan options
+    pub value_type: ValueType,
+    pub endianness: Endianness,
+    pub alignment: usize,
+    pub compare_mode: CompareMode,
+    pub compare_against: CompareAgainst,
+    pub aob_executable_only: bool,
+    pub case_insensitive: bool,
+    pub scan_threads: usize,
+
+    // Address list
+    pub address_list: Vec<AddressEntry>,
+    pub address_table_state: TableState,
+    /// Set while the description of an entry is being typed
+    pub address_input: Option<String>,
+
+    // Hex view
+    pub hex_view: HexView,
+
+    // Struct dissector
+    pub struct_defs: Vec<StructDef>,
+    pub struct_view: StructView,
+
+    // Writes made, to undo them
+    pub journal: WriteJournal,
+
+    // Freezing
+    pub freezer: Option<Freezer>,
+    pub freeze_mode: FreezeMode,
+    /// When the freezer's rewrites were last added to the audit log
+    pub freeze_audited: Instant,
+
+    // Pointer scan
+    /// The result pointers are looked for to
+    pub pointer_target: Option<SearchLocation>,
+    pub pointer_config: PointerScanConfig,
+    pub pointer_chains: Vec<PointerChain>,
+    /// Where each chain led when last followed
+    pub pointer_resolved: Vec<Option<usize>>,
+    pub pointer_table_state: TableState,
 
     // UI Sfuff
     pub sub_screen: SubScreen,
     pub map_table_state: TableState,
+    /// Set while a name glob is being typed in the maps popup
+    pub map_glob: Option<String>,
     pub search_table_state: TableState,
+    /// Set while an address to go to is being typed
+    pub goto_input: Option<String>,
     pub ui_msg: Option<String>,
 
     // Value editing shit
     pub selected_value: Option<SearchLocation>,
     pub new_value: String,
+    /// How strings shorter than the value being edited are written
+    pub str_fill: StrFill,
 }
 
 #[derive(Debug)]
@@ -81,9 +147,26 @@ impl Kunai {
             None => self.tasks.task_list[index].clone(), // If not seraching, select normally
         };
 
+        self.memedit.cancel_scan();
+        self.memedit.scan_job = None;
+        self.memedit.audit_freeze_rewrites();
         self.memedit.task = task.clone();
+        self.memedit.snapshot = None;
         self.memedit.task_mem = TaskMemory::new();
         self.memedit.task_mem.populate_info(&task.pid);
+        self.memedit.freezer = Some(Freezer::start(&task.pid)); // Stops the old one
+        self.memedit.journal = WriteJournal::new();
+        self.memedit.pointer_config.width = pointer_width(&task.pid);
+        self.memedit.pointer_target = None;
+        self.memedit.pointer_chains = Vec::new();
+        self.memedit.pointer_resolved = Vec::new();
+        // Addresses of the old process mean nothing in the new one, editing them would write there
+        self.memedit.search_list = Vec::new();
+        self.memedit.search_table_state.select(None);
+        self.memedit.selected_value = None;
+        self.memedit.hex_view = HexView::new();
+        self.memedit.struct_view = StructView::new();
+        self.memedit.sub_screen = SubScreen::MemorySearch;
 
         self.current_screen = CurrentScreen::MemoryEditingScreen;
     }
@@ -217,98 +300,1413 @@ impl MemoryEditor {
             task_mem: TaskMemory::new(),
             sub_screen: SubScreen::MemorySearch,
             map_table_state: TableState::new(),
+            map_glob: None,
             search_table_state: TableState::new(),
+            goto_input: None,
             search_string: String::new(),
             search_list: Vec::new(),
+            snapshot: None,
+            scan_job: None,
+            value_type: ValueType::Str(StrEncoding::Utf8),
+            endianness: Endianness::Little,
+            alignment: 1,
+            compare_mode: CompareMode::Equal,
+            compare_against: CompareAgainst::Previous,
+            aob_executable_only: true,
+            case_insensitive: false,
+            scan_threads: default_threads(),
+            address_list: Vec::new(),
+            address_table_state: TableState::new(),
+            address_input: None,
+            hex_view: HexView::new(),
+            struct_defs: Vec::new(),
+            struct_view: StructView::new(),
+            journal: WriteJournal::new(),
+            freezer: None,
+            freeze_audited: Instant::now(),
+            freeze_mode: FreezeMode::Exact,
+            pointer_target: None,
+            pointer_config: PointerScanConfig {
+                max_depth: 4,
+                max_offset: 0x1000,
+                width: std::mem::size_of::<usize>(),
+            },
+            pointer_chains: Vec::new(),
+            pointer_resolved: Vec::new(),
+            pointer_table_state: TableState::new(),
             ui_msg: None,
             selected_value: None,
             new_value: String::new(),
+            str_fill: StrFill::NulTerminated,
         }
     }
 
     /// Refreshes the previously searched list
     /// while adding prev value
     pub fn refresh_list(&mut self) {
-        for location in &mut self.search_list {
-            let new_value = match read_mem(&self.task.pid, location.start, location.end) {
-                Ok(v) => {
-                    trace_dbg!(&v);
-                    bytes_to_string(v)
-                }
+        let ranges: Vec<(usize, usize)> =
+            self.search_list.iter().map(|l| (l.start, l.end)).collect();
+        let values = read_batch(&self.task.pid, &ranges);
+
+        for (location, value) in self.search_list.iter_mut().zip(values) {
+            match value {
+                Ok(v) => location.update(v),
                 Err(e) => {
                     trace_dbg!(e);
-                    continue;
                 }
             };
-            // TODO: clone?
-            (location.value, location.prev_value) = (new_value, location.value.clone());
         }
     }
 
+    /// Narrows down `search_list` (or the snapshot) in the background,
+    /// keeping only the locations matching the next scan comparison
+    pub fn next_scan(&mut self) {
+        if self.is_scanning() {
+            return;
+        }
+
+        let (value_type, endianness) = match (&self.snapshot, self.search_list.first()) {
+            (Some(s), _) => (s.value_type, s.endianness),
+            (None, Some(l)) => (l.value_type, l.endianness),
+            (None, None) => {
+                self.ui_msg = Some("Nothing to narrow down, search first".to_string());
+                return;
+            }
+        };
+
+        let scan = match NextScan::new(
+            self.compare_mode,
+            self.compare_against,
+            &self.search_string,
+            value_type,
+            endianness,
+        ) {
+            Ok(s) => s,
+            Err(e) => {
+                self.ui_msg = Some(e);
+                return;
+            }
+        };
+
+        // Results in maps deselected since the last scan are dropped
+        let maps = &self.task_mem;
+        let request = match self.snapshot.take() {
+            Some(mut snapshot) => {
+                snapshot.retain_regions(|m| maps.is_selected(m));
+                ScanRequest::NextSnapshot { scan, snapshot }
+            }
+            None => {
+                let mut locations = std::mem::take(&mut self.search_list);
+                locations.retain(|l| maps.is_selected(&l.mem_info));
+                ScanRequest::Next { scan, locations }
+            }
+        };
+
+        self.ui_msg = Some("Narrowing down...".to_string());
+        self.start_scan(request);
+    }
+
+    /// Starts an unknown initial value scan by snapshotting every selected readable map
+    pub fn unknown_value_scan(&mut self) {
+        if self.is_scanning() {
+            return;
+        }
+
+        if self.task_mem.selected_count() == 0 {
+            self.ui_msg = Some("No maps selected".to_string());
+            return;
+        }
+
+        self.search_list = Vec::new();
+        self.snapshot = None; // Removes the previous snapshot from disk
+
+        self.ui_msg = Some("Taking snapshot...".to_string());
+        self.start_scan(ScanRequest::Unknown {
+            maps: self.task_mem.maps.clone(),
+            value_type: self.value_type,
+            endianness: self.endianness,
+            alignment: self.alignment,
+        });
+    }
+
     pub fn search_memory(&mut self) {
-        // TODO: This message isn't visible
-        self.ui_msg = Some(format!("Searching: {}", self.search_string));
-        let mut locations = Vec::new();
+        if self.is_scanning() {
+            return;
+        }
 
-        let pid = &self.task.pid;
-        let search_string = &self.search_string;
+        if self.task_mem.selected_count() == 0 {
+            self.ui_msg = Some("No maps selected".to_string());
+            return;
+        }
+
+        let mut query = match SearchQuery::new(
+            &self.search_string,
+            self.value_type,
+            self.endianness,
+            self.alignment,
+        ) {
+            Ok(q) => q,
+            Err(e) => {
+                self.ui_msg = Some(e);
+                return;
+            }
+        };
+        query.executable_only = self.value_type == ValueType::Aob && self.aob_executable_only;
 
-        for map in &self.task_mem.maps {
-            let locs = match search_mem(pid, search_string, map) {
-                Ok(l) => l,
+        if self.case_insensitive {
+            query = match query.ignore_case(&self.search_string) {
+                Ok(q) => q,
                 Err(e) => {
-                    trace_dbg!(e);
-                    continue;
+                    self.ui_msg = Some(e);
+                    return;
                 }
             };
+        }
+
+        self.ui_msg = Some(format!("Searching: {}", self.search_string));
+        self.search_list = Vec::new();
+        self.snapshot = None;
+
+        self.start_scan(ScanRequest::First {
+            query,
+            maps: self.task_mem.maps.clone(),
+        });
+    }
+
+    fn start_scan(&mut self, request: ScanRequest) {
+        self.search_table_state.select(None);
+        self.scan_job = Some(ScanJob::spawn(&self.task.pid, request, self.scan_threads));
+    }
+
+    pub fn is_scanning(&self) -> bool {
+        self.scan_job.is_some()
+    }
+
+    /// Asks the running scan to stop, it hands back what it was given
+    pub fn cancel_scan(&mut self) {
+        if let Some(job) = &self.scan_job {
+            job.cancel();
+            self.ui_msg = Some("Cancelling scan...".to_string());
+        }
+    }
+
+    /// Picks up progress and results from the background scan, called every tick
+    pub fn poll_scan(&mut self) {
+        let job = match &mut self.scan_job {
+            Some(j) => j,
+            None => return,
+        };
+
+        let (outcome, msg) = match job.poll() {
+            Some(done) => done,
+            None => return,
+        };
 
-            locations.extend(locs);
+        match outcome {
+            ScanOutcome::Locations(locations) => self.search_list = locations,
+            ScanOutcome::Snapshot(snapshot) => self.snapshot = Some(snapshot),
+            ScanOutcome::Pointers(chains) => {
+                self.pointer_chains = chains;
+                self.pointer_resolved = Vec::new();
+            }
+            ScanOutcome::Failed => {
+                trace_dbg!(&msg);
+            }
         }
-        std::thread::sleep(std::time::Duration::from_secs(5));
 
-        self.ui_msg = Some(format!("Found {} occurances!", locations.len()));
-        self.search_list = locations;
+        self.ui_msg = Some(msg);
+        self.scan_job = None;
     }
 
-    pub fn edit_memory(&mut self) {
+    /// Opens the pointer scan popup for the highlighted search result
+    pub fn open_pointer_scan(&mut self) {
+        let location = match self.search_table_state.selected() {
+            Some(idx) => &self.search_list[idx],
+            None => {
+                self.ui_msg = Some("Highlight a result to find pointers to".to_string());
+                return;
+            }
+        };
+
+        if self.pointer_target.as_ref().map(|t| t.start) != Some(location.start) {
+            self.pointer_target = Some(location.clone());
+            self.pointer_chains = Vec::new();
+            self.pointer_table_state.select(None);
+        }
+        self.sub_screen = SubScreen::PointerScan;
+    }
+
+    /// Looks for pointer chains from static module addresses to the pointer target
+    pub fn pointer_scan(&mut self) {
+        if self.is_scanning() {
+            return;
+        }
+
+        let target = match &self.pointer_target {
+            Some(t) => t.start,
+            None => return,
+        };
+
+        self.ui_msg = Some(format!("Looking for pointers to {}...", format_hex(target)));
+        self.pointer_chains = Vec::new();
+        self.pointer_table_state.select(None);
+        self.scan_job = Some(ScanJob::spawn(
+            &self.task.pid,
+            ScanRequest::Pointers {
+                target,
+                maps: self.task_mem.maps.clone(),
+                config: self.pointer_config,
+            },
+            self.scan_threads,
+        ));
+    }
+
+    /// Follows every chain again, to check which still lead to the target
+    pub fn resolve_pointer_chains(&mut self) {
+        self.pointer_resolved = self
+            .pointer_chains
+            .iter()
+            .map(|c| {
+                c.resolve(
+                    &self.task.pid,
+                    &self.task_mem.maps,
+                    self.pointer_config.width,
+                )
+            })
+            .collect();
+
+        let valid = self
+            .pointer_resolved
+            .iter()
+            .filter(|r| r.is_some() && **r == self.pointer_target.as_ref().map(|t| t.start))
+            .count();
+        self.ui_msg = Some(format!(
+            "{} of {} chains still lead to the target",
+            valid,
+            self.pointer_chains.len()
+        ));
+    }
+
+    /// Adds the highlighted chain to the address list, as a pointer expression
+    pub fn add_chain_to_address_list(&mut self) {
+        let (chain, target) = match (self.pointer_table_state.selected(), &self.pointer_target) {
+            (Some(idx), Some(target)) => (&self.pointer_chains[idx], target),
+            _ => {
+                self.ui_msg = Some("Highlight a chain to add".to_string());
+                return;
+            }
+        };
+
+        let mut entry = AddressEntry::from_location(target, &self.task_mem.maps);
+        entry.address = chain.expression(&self.task_mem.maps);
+        self.ui_msg = Some(format!("Added {} to the address list", entry.address));
+        self.address_list.push(entry);
+    }
+
+    /// Saves the chains of the last pointer scan for this process
+    pub fn save_pointer_map(&mut self) {
+        if self.pointer_chains.is_empty() {
+            self.ui_msg = Some("No chains to save, scan first".to_string());
+            return;
+        }
+
+        let path = pointer_map_path(&self.task.name);
+        self.ui_msg = match save_chains(&path, &self.pointer_chains) {
+            Ok(_) => Some(format!(
+                "Saved {} chains to {}",
+                self.pointer_chains.len(),
+                path.display()
+            )),
+            Err(e) => {
+                let msg = format!("Failed to save pointer map: {}", e);
+                trace_dbg!(e);
+                Some(msg)
+            }
+        };
+    }
+
+    /// Keeps the saved chains of this process that lead to the pointer target in this run too.
+    /// Saving the survivors and comparing again after the next restart narrows them down further.
+    pub fn compare_pointer_map(&mut self) {
+        let target = match &self.pointer_target {
+            Some(t) => t.start,
+            None => return,
+        };
+
+        let path = pointer_map_path(&self.task.name);
+        let saved = match load_chains(&path) {
+            Ok(c) => c,
+            Err(e) => {
+                self.ui_msg = Some(format!("Failed to load {}: {}", path.display(), e));
+                trace_dbg!(e);
+                return;
+            }
+        };
+
+        let width = self.pointer_config.width;
+        let survivors: Vec<PointerChain> = saved
+            .iter()
+            .filter(|c| c.resolve(&self.task.pid, &self.task_mem.maps, width) == Some(target))
+            .cloned()
+            .collect();
+
+        self.ui_msg = Some(format!(
+            "{} of {} saved chains also lead to {}",
+            survivors.len(),
+            saved.len(),
+            format_hex(target)
+        ));
+        self.pointer_resolved = vec![Some(target); survivors.len()];
+        self.pointer_chains = survivors;
+        self.pointer_table_state.select(None);
+    }
+
+    pub fn cycle_pointer_depth(&mut self) {
+        self.pointer_config.max_depth = self.pointer_config.max_depth % 7 + 1;
+    }
+
+    pub fn cycle_pointer_offset(&mut self) {
+        self.pointer_config.max_offset = match self.pointer_config.max_offset {
+            0x100 => 0x400,
+            0x400 => 0x1000,
+            0x1000 => 0x4000,
+            _ => 0x100,
+        };
+    }
+
+    pub fn toggle_selected_map(&mut self) {
+        if let Some(idx) = self.map_table_state.selected() {
+            self.task_mem.toggle_map(idx);
+        }
+    }
+
+    pub fn apply_map_preset(&mut self, preset: MapPreset) {
+        self.task_mem.apply_preset(&preset);
+        self.ui_msg = Some(format!(
+            "{} of {} maps selected",
+            self.task_mem.selected_count(),
+            self.task_mem.maps.len()
+        ));
+    }
+
+    /// Selects only the maps of the module the highlighted map belongs to
+    pub fn select_module_maps(&mut self) {
+        let name = match self.map_table_state.selected() {
+            Some(idx) => self.task_mem.maps[idx].name.clone(),
+            None => {
+                self.ui_msg = Some("Highlight a map of the module first".to_string());
+                return;
+            }
+        };
+
+        if !name.starts_with('/') {
+            self.ui_msg = Some(format!("'{}' is not a module", name));
+            return;
+        }
+
+        self.apply_map_preset(MapPreset::Module(name));
+    }
+
+    /// Selects the maps with a name matching the glob typed so far
+    pub fn apply_map_glob(&mut self) {
+        if let Some(glob) = self.map_glob.take() {
+            self.apply_map_preset(MapPreset::Glob(glob));
+        }
+    }
+
+    /// Highlights the result at the typed address.
+    /// Addresses that aren't a result yet are read as the current type and added as one.
+    pub fn goto_address(&mut self) {
+        let input = match self.goto_input.take() {
+            Some(i) => i,
+            None => return,
+        };
+
+        let addr = match evaluate(&self.eval_context(), &input) {
+            Ok(a) => a,
+            Err(e) => {
+                self.ui_msg = Some(e);
+                return;
+            }
+        };
+
+        match self.sub_screen {
+            SubScreen::HexView => return self.hex_view.jump(addr),
+            SubScreen::StructView => return self.struct_view.open(addr),
+            _ => {}
+        }
+
+        if let Some(idx) = self
+            .search_list
+            .iter()
+            .position(|l| l.start <= addr && addr < l.end.max(l.start + 1))
+        {
+            self.search_table_state.select(Some(idx));
+            return;
+        }
+
+        // Next scans read every location as the type of the list
+        if let Some(first) = self.search_list.first() {
+            if (first.value_type, first.endianness) != (self.value_type, self.endianness) {
+                self.ui_msg = Some(format!(
+                    "The search list holds {} {} values, switch to that type to add an address",
+                    first.endianness, first.value_type
+                ));
+                return;
+            }
+        }
+
+        let size = match self.value_type.size() {
+            Some(s) => s,
+            None => {
+                self.ui_msg = Some(format!(
+                    "Can't go to an address as '{}', pick a number type",
+                    self.value_type
+                ));
+                return;
+            }
+        };
+        let map = match find_map(&self.task_mem.maps, addr) {
+            Some(m) => m.clone(),
+            None => {
+                self.ui_msg = Some(format!("{} isn't mapped", input.trim()));
+                return;
+            }
+        };
+
+        let mut location = SearchLocation::new();
+        location.start = addr;
+        location.end = addr + size;
+        location.value_type = self.value_type;
+        location.endianness = self.endianness;
+        location.mem_info = map;
+        match self.read_value(&location) {
+            Ok(bytes) => {
+                location.value = location.decode(&bytes);
+                location.first_bytes = bytes.clone();
+                location.bytes = bytes;
+            }
+            Err(e) => {
+                self.ui_msg = Some(e);
+                return;
+            }
+        }
+
+        self.search_list.push(location);
+        self.search_table_state
+            .select(Some(self.search_list.len() - 1));
+    }
+
+    /// Freezes the highlighted result at its current value, or unfreezes it
+    pub fn toggle_freeze(&mut self) {
+        let location = match self.search_table_state.selected() {
+            Some(idx) => self.search_list[idx].clone(),
+            None => {
+                self.ui_msg = Some("Highlight a result to freeze".to_string());
+                return;
+            }
+        };
+
+        self.toggle_location_freeze(&location);
+    }
+
+    fn toggle_location_freeze(&mut self, location: &SearchLocation) {
+        let freeze = self.read_value(location).and_then(|bytes| {
+            Freeze::new(
+                location.start,
+                location.value_type,
+                location.endianness,
+                bytes,
+                self.freeze_mode,
+            )
+        });
+
+        self.freeze(freeze);
+    }
+
+    /// The current bytes at a location
+    fn read_value(&self, location: &SearchLocation) -> Result<Vec<u8>, String> {
+        match read_batch(&self.task.pid, &[(location.start, location.end)]).pop() {
+            Some(Ok(b)) => Ok(b),
+            Some(Err(e)) => Err(format!("Failed to read value: {}", e)),
+            None => Err("Nothing to read".to_string()),
+        }
+    }
+
+    /// Freezes the value being edited at the typed value
+    pub fn freeze_new_value(&mut self) {
         let location = match &self.selected_value {
             Some(l) => l,
+            None => return,
+        };
+
+        let freeze = check_writable()
+            .and_then(|_| self.new_value_bytes())
+            .and_then(|bytes| {
+                Freeze::new(
+                    location.start,
+                    location.value_type,
+                    location.endianness,
+                    bytes,
+                    self.freeze_mode,
+                )
+            });
+
+        match (&self.freezer, freeze) {
+            (Some(freezer), Ok(f)) => {
+                self.ui_msg = Some(format!("Frozen {} ({})", format_hex(f.addr), f.label()));
+                freezer.insert(f);
+            }
+            (_, Err(e)) => self.ui_msg = Some(e),
+            (None, _) => {}
+        }
+    }
+
+    fn freeze(&mut self, freeze: Result<Freeze, String>) {
+        let freeze = match freeze {
+            Ok(f) => f,
+            Err(e) => {
+                self.ui_msg = Some(e);
+                return;
+            }
+        };
+
+        let freezer = match &self.freezer {
+            Some(f) => f,
+            None => return,
+        };
+
+        // Unfreezing is always fine
+        if freezer.get(freeze.addr).is_none() {
+            if let Err(e) = check_writable() {
+                self.ui_msg = Some(e);
+                return;
+            }
+        }
+
+        let (addr, label) = (freeze.addr, freeze.label());
+        self.ui_msg = match freezer.toggle(freeze) {
+            true => Some(format!("Frozen {} ({})", format_hex(addr), label)),
+            false => Some(format!("Unfrozen {}", format_hex(addr))),
+        };
+    }
+
+    /// What address expressions of this process are evaluated against
+    pub fn eval_context(&self) -> EvalContext<'_> {
+        EvalContext {
+            pid: &self.task.pid,
+            maps: &self.task_mem.maps,
+            width: self.pointer_config.width,
+        }
+    }
+
+    /// Adds the highlighted result to the address list
+    pub fn add_to_address_list(&mut self) {
+        let location = match self.search_table_state.selected() {
+            Some(idx) => &self.search_list[idx],
             None => {
-                self.ui_msg = Some("Invalid memory address".to_string());
+                self.ui_msg = Some("Highlight a result to add".to_string());
                 return;
             }
         };
 
-        let mem_file = "/proc/".to_string() + &self.task.pid + "/mem";
-        let mut mem = match File::options().read(true).write(true).open(mem_file) {
-            Ok(f) => f,
+        let entry = AddressEntry::from_location(location, &self.task_mem.maps);
+        self.ui_msg = Some(format!("Added {} to the address list", entry.address));
+        self.address_list.push(entry);
+    }
+
+    fn selected_address(&self) -> Option<&AddressEntry> {
+        self.address_list.get(self.address_table_state.selected()?)
+    }
+
+    /// Re-reads the values of the address list while it's shown, called every tick
+    pub fn refresh_address_list(&mut self) {
+        if self.sub_screen != SubScreen::AddressList || self.address_list.is_empty() {
+            return;
+        }
+
+        let ctx = self.eval_context();
+        let resolved: Vec<Result<usize, String>> =
+            self.address_list.iter().map(|e| e.resolve(&ctx)).collect();
+        let ranges: Vec<(usize, usize)> = self
+            .address_list
+            .iter()
+            .zip(&resolved)
+            .map(|(e, addr)| addr.as_ref().map_or((0, 0), |a| (*a, a + e.length)))
+            .collect();
+        let values = read_batch(&self.task.pid, &ranges);
+
+        for ((entry, addr), value) in self.address_list.iter_mut().zip(resolved).zip(values) {
+            entry.resolved = addr.as_ref().ok().copied();
+            entry.value = match (addr, value) {
+                (Err(e), _) => e,
+                (Ok(_), Ok(bytes)) => entry.value_type.decode(&bytes, entry.endianness),
+                (Ok(_), Err(e)) => {
+                    trace_dbg!(e);
+                    "??".to_string()
+                }
+            };
+        }
+    }
+
+    pub fn remove_address(&mut self) {
+        if let Some(idx) = self.address_table_state.selected() {
+            self.address_list.remove(idx);
+            if idx >= self.address_list.len() {
+                self.address_table_state
+                    .select(self.address_list.len().checked_sub(1));
+            }
+        }
+    }
+
+    /// Opens the value editor for the highlighted entry
+    pub fn edit_address(&mut self) {
+        let location = match self.selected_address() {
+            Some(e) => e.location(&self.eval_context()),
+            None => return,
+        };
+
+        match location {
+            Ok(l) => {
+                self.selected_value = Some(l);
+                self.sub_screen = SubScreen::ValueEditing;
+            }
+            Err(e) => self.ui_msg = Some(e),
+        }
+    }
+
+    /// Sets the group of the highlighted entry, `None` removes it from its group
+    pub fn set_address_group(&mut self, group: Option<char>) {
+        if let Some(idx) = self.address_table_state.selected() {
+            self.address_list[idx].group = group.map_or(String::new(), |g| g.to_string());
+        }
+    }
+
+    pub fn start_address_description(&mut self) {
+        if let Some(entry) = self.selected_address() {
+            self.address_input = Some(entry.description.clone());
+        }
+    }
+
+    pub fn apply_address_description(&mut self) {
+        if let (Some(description), Some(idx)) = (
+            self.address_input.take(),
+            self.address_table_state.selected(),
+        ) {
+            self.address_list[idx].description = description;
+        }
+    }
+
+    /// Freezes the highlighted entry at its current value, or unfreezes it
+    pub fn toggle_address_freeze(&mut self) {
+        let location = match self.selected_address() {
+            Some(e) => e.location(&self.eval_context()),
+            None => return,
+        };
+
+        match location {
+            Ok(l) => self.toggle_location_freeze(&l),
+            Err(e) => self.ui_msg = Some(e),
+        }
+    }
+
+    /// Freezes every entry in the group of the highlighted one,
+    /// or unfreezes them if they all are frozen already
+    pub fn toggle_group_freeze(&mut self) {
+        let group = match self.selected_address() {
+            Some(e) if !e.group.is_empty() => e.group.clone(),
+            Some(_) => return self.toggle_address_freeze(),
+            None => return,
+        };
+        let freezer = match &self.freezer {
+            Some(f) => f,
+            None => return,
+        };
+
+        let mut locations = Vec::new();
+        for entry in self.address_list.iter().filter(|e| e.group == group) {
+            match entry.location(&self.eval_context()) {
+                Ok(l) => locations.push(l),
+                Err(e) => {
+                    self.ui_msg = Some(e);
+                    return;
+                }
+            }
+        }
+
+        if locations.iter().all(|l| freezer.get(l.start).is_some()) {
+            for location in &locations {
+                freezer.remove(location.start);
+            }
+            self.ui_msg = Some(format!("Unfroze group {}", group));
+            return;
+        }
+        if let Err(e) = check_writable() {
+            self.ui_msg = Some(e);
+            return;
+        }
+
+        for location in locations.iter().filter(|l| freezer.get(l.start).is_none()) {
+            let freeze = self.read_value(location).and_then(|bytes| {
+                Freeze::new(
+                    location.start,
+                    location.value_type,
+                    location.endianness,
+                    bytes,
+                    self.freeze_mode,
+                )
+            });
+            match freeze {
+                Ok(f) => freezer.insert(f),
+                Err(e) => {
+                    self.ui_msg = Some(e);
+                    return;
+                }
+            }
+        }
+        self.ui_msg = Some(format!("Froze group {}", group));
+    }
+
+    /// Saves the address list, with what's frozen, to the table of this process
+    pub fn save_address_table(&mut self) {
+        let entries: Vec<(&AddressEntry, Option<Freeze>)> = self
+            .address_list
+            .iter()
+            .map(|e| {
+                let freeze = match (&self.freezer, e.resolve(&self.eval_context())) {
+                    (Some(freezer), Ok(addr)) => freezer.get(addr),
+                    _ => None,
+                };
+                (e, freeze)
+            })
+            .collect();
+
+        let path = table_path(&self.task.name);
+        self.ui_msg = match save_table(&path, &entries) {
+            Ok(_) => Some(format!(
+                "Saved {} addresses to {}",
+                entries.len(),
+                path.display()
+            )),
+            Err(e) => Some(format!("Failed to save {}: {}", path.display(), e)),
+        };
+    }
+
+    /// Replaces the address list with the table of this process, freezing what was frozen
+    pub fn load_address_table(&mut self) {
+        let path = table_path(&self.task.name);
+        let entries = match load_table(&path) {
+            Ok(e) => e,
             Err(e) => {
-                self.ui_msg = Some("Failed to open mem file".to_string());
-                trace_dbg!(e);
+                self.ui_msg = Some(format!("Failed to load {}: {}", path.display(), e));
                 return;
             }
         };
 
-        let seek = location.start;
-        let new_val = self.new_value.as_bytes();
+        let mut failed = 0;
+        for (entry, saved) in &entries {
+            let saved = match saved {
+                Some(s) => s,
+                None => continue,
+            };
+
+            let freeze = check_writable()
+                .and_then(|_| entry.resolve(&self.eval_context()))
+                .and_then(|addr| {
+                    // Absolute addresses from an older run can point anywhere now
+                    if !self
+                        .task_mem
+                        .maps
+                        .iter()
+                        .any(|m| m.start <= addr && addr < m.end)
+                    {
+                        return Err(format!("{} isn't mapped", entry.address));
+                    }
+                    Freeze::new(
+                        addr,
+                        entry.value_type,
+                        entry.endianness,
+                        saved.bytes.clone(),
+                        saved.mode,
+                    )
+                });
+            match (&self.freezer, freeze) {
+                (Some(freezer), Ok(f)) => freezer.insert(f),
+                (_, Err(e)) => {
+                    trace_dbg!(e);
+                    failed += 1;
+                }
+                (None, _) => {}
+            }
+        }
+
+        self.address_list = entries.into_iter().map(|(e, _)| e).collect();
+        self.address_table_state.select(None);
+        self.ui_msg = Some(match failed {
+            0 => format!("Loaded {} addresses", self.address_list.len()),
+            n if is_read_only() => format!(
+                "Loaded {} addresses, {} freezes weren't restored in read-only mode",
+                self.address_list.len(),
+                n
+            ),
+            n => format!(
+                "Loaded {} addresses, {} freezes couldn't be restored",
+                self.address_list.len(),
+                n
+            ),
+        });
+    }
+
+    pub fn cycle_freeze_mode(&mut self) {
+        self.freeze_mode = self.freeze_mode.next();
+    }
+
+    pub fn cycle_freeze_interval(&mut self) {
+        if let Some(freezer) = &self.freezer {
+            freezer.cycle_interval();
+        }
+    }
+
+    /// Drops the freezer once the target is gone, called every tick
+    pub fn poll_freezer(&mut self) {
+        if self.freeze_audited.elapsed() >= FREEZE_AUDIT_INTERVAL {
+            self.audit_freeze_rewrites();
+        }
+
+        let count = match &self.freezer {
+            Some(freezer) if freezer.has_exited() => freezer.count(),
+            _ => return,
+        };
+
+        // Its last rewrites go with it otherwise
+        self.audit_freeze_rewrites();
+        self.freezer = None;
+        self.ui_msg = Some(format!("Process exited, {} frozen values released", count));
+    }
+
+    /// Adds what the freezer rewrote since last time to the audit log
+    pub fn audit_freeze_rewrites(&mut self) {
+        self.freeze_audited = Instant::now();
+        let rewrites = match &self.freezer {
+            Some(f) => f.take_rewrites(),
+            None => return,
+        };
+
+        for r in rewrites {
+            if let Err(e) = self.audit("freeze", r.addr, &r.old, &r.new, Some(r.count)) {
+                self.ui_msg = Some(e);
+            }
+        }
+    }
+
+    /// Turns read-only mode on or off, frozen values stay but aren't written meanwhile
+    pub fn toggle_read_only(&mut self) {
+        self.ui_msg = Some(match toggle_read_only() {
+            Ok(true) => match self.freezer.as_ref().map_or(0, |f| f.count()) {
+                0 => "Read-only mode, nothing gets written".to_string(),
+                n => format!("Read-only mode, nothing gets written, {} freezes paused", n),
+            },
+            Ok(false) => "Writes allowed".to_string(),
+            Err(e) => e,
+        });
+    }
+
+    pub fn cycle_value_type(&mut self) {
+        self.value_type = self.value_type.next();
+        // Numbers are usually naturally aligned, strings aren't
+        self.alignment = self.value_type.size().unwrap_or(1);
+    }
+
+    pub fn toggle_endianness(&mut self) {
+        self.endianness = self.endianness.toggle();
+    }
+
+    /// Switches between string encodings, only when searching for strings
+    pub fn cycle_encoding(&mut self) {
+        if let ValueType::Str(encoding) = self.value_type {
+            self.value_type = ValueType::Str(encoding.next());
+        }
+    }
+
+    pub fn toggle_case_insensitive(&mut self) {
+        self.case_insensitive = !self.case_insensitive;
+    }
+
+    pub fn toggle_aob_executable_only(&mut self) {
+        self.aob_executable_only = !self.aob_executable_only;
+    }
+
+    pub fn cycle_compare_mode(&mut self) {
+        self.compare_mode = self.compare_mode.next();
+    }
+
+    pub fn toggle_compare_against(&mut self) {
+        self.compare_against = self.compare_against.toggle();
+    }
+
+    pub fn cycle_alignment(&mut self) {
+        self.alignment = match self.alignment {
+            1 => 2,
+            2 => 4,
+            4 => 8,
+            _ => 1,
+        };
+    }
+
+    /// Opens the hex view at the highlighted result
+    pub fn open_hex_view(&mut self) {
+        match self.search_table_state.selected() {
+            Some(idx) => self.hex_view.jump(self.search_list[idx].start),
+            None => {
+                self.ui_msg =
+                    Some("Highlight a result to view, or press g in the hex view".to_string())
+            }
+        }
+        self.sub_screen = SubScreen::HexView;
+    }
 
-        match mem.seek(SeekFrom::Start(seek as u64)) {
-            Ok(_) => {}
+    /// Opens the hex view at the highlighted address list entry
+    pub fn open_hex_view_at_address(&mut self) {
+        let addr = match self.selected_address() {
+            Some(e) => e.resolve(&self.eval_context()),
+            None => return,
+        };
+
+        match addr {
+            Ok(a) => {
+                self.hex_view.jump(a);
+                self.sub_screen = SubScreen::HexView;
+            }
+            Err(e) => self.ui_msg = Some(e),
+        }
+    }
+
+    /// Re-reads the hex view while it's shown, called every tick
+    pub fn refresh_hex_view(&mut self) {
+        if self.sub_screen == SubScreen::HexView {
+            self.hex_view.refresh(&self.task.pid);
+        }
+    }
+
+    /// Types a hex digit over the byte under the cursor, writing it once both digits are in
+    pub fn hex_edit(&mut self, digit: u8) {
+        if let Err(e) = check_writable() {
+            self.ui_msg = Some(e);
+            return;
+        }
+
+        let addr = self.hex_view.cursor;
+        if self.hex_view.byte_at(addr).is_none() {
+            self.ui_msg = Some(format!(
+                "Can't edit {}, it isn't readable",
+                format_hex(addr)
+            ));
+            return;
+        }
+
+        let byte = match self.hex_view.type_digit(digit) {
+            Some(b) => b,
+            None => return,
+        };
+
+        match self.write_memory("hex edit", addr, &[byte]) {
+            Ok(_) => {
+                self.hex_view.refresh(&self.task.pid);
+                self.hex_view.move_cursor(1);
+            }
             Err(e) => {
-                self.ui_msg = Some("Failed to seek mem file".to_string());
+                self.ui_msg = Some(e.clone());
                 trace_dbg!(e);
+            }
+        }
+    }
+
+    /// Shows the memory at `addr` as a struct, reading the definitions again
+    fn open_struct_view(&mut self, addr: usize) {
+        self.reload_structs();
+        self.struct_view.open(addr);
+        self.sub_screen = SubScreen::StructView;
+    }
+
+    /// Opens the struct view at the highlighted result
+    pub fn open_struct_view_at_result(&mut self) {
+        match self.search_table_state.selected() {
+            Some(idx) => self.open_struct_view(self.search_list[idx].start),
+            None => self.ui_msg = Some("Highlight a result to dissect".to_string()),
+        }
+    }
+
+    /// Opens the struct view at the hex view cursor
+    pub fn open_struct_view_at_cursor(&mut self) {
+        self.open_struct_view(self.hex_view.cursor);
+    }
+
+    pub fn reload_structs(&mut self) {
+        let path = structs_path();
+        match load_structs(&path) {
+            Ok(defs) => {
+                if defs.is_empty() {
+                    self.ui_msg = Some(format!(
+                        "No structs defined yet, add some to {}",
+                        path.display()
+                    ));
+                }
+                self.struct_defs = defs;
+                if self.struct_view.def >= self.struct_defs.len() {
+                    self.struct_view.def = 0;
+                }
+            }
+            Err(e) => self.ui_msg = Some(format!("Failed to load {}: {}", path.display(), e)),
+        }
+    }
+
+    /// Guesses a struct layout for the memory at the struct view address,
+    /// showing it as a draft definition
+    pub fn auto_dissect(&mut self) {
+        let addr = match self.struct_view.addr {
+            Some(a) => a,
+            None => return,
+        };
+
+        // Structs near the end of a map would fail to read as a whole
+        let end = match find_map(&self.task_mem.maps, addr) {
+            Some(map) => map.end.min(addr.saturating_add(GUESS_SIZE)),
+            None => {
+                self.ui_msg = Some(format!("{} isn't in a mapped region", format_hex(addr)));
                 return;
             }
+        };
+        let bytes = match read_batch(&self.task.pid, &[(addr, end)]).pop() {
+            Some(Ok(b)) => b,
+            Some(Err(e)) => {
+                self.ui_msg = Some(format!("Failed to read {}: {}", format_hex(addr), e));
+                return;
+            }
+            None => return,
+        };
+
+        // A saved struct keeps its name, the draft of the same spot gets another one
+        let name = unique_name(&self.struct_defs, &format!("auto_{:x}", addr));
+        let def = guess_layout(
+            &name,
+            &bytes,
+            &self.task_mem.maps,
+            self.pointer_config.width,
+        );
+        self.ui_msg = Some(format!(
+            "Guessed {} fields in 0x{:X} bytes, y retypes a field, s saves",
+            def.fields.len(),
+            bytes.len()
+        ));
+
+        self.struct_defs.retain(|d| d.name != name);
+        self.struct_defs.push(def);
+        self.struct_view.def = self.struct_defs.len() - 1;
+        self.struct_view.expanded.clear();
+    }
+
+    /// Gives the highlighted field of a draft the next type
+    pub fn cycle_struct_field_type(&mut self) {
+        let path = match self.struct_view.selected_row() {
+            Some(row) => row.path.clone(),
+            None => return,
+        };
+        let def = match self.struct_defs.get_mut(self.struct_view.def) {
+            Some(d) if d.draft && path.len() == 1 => d,
+            _ => {
+                self.ui_msg =
+                    Some("Only fields of a guessed struct can be retyped here".to_string());
+                return;
+            }
+        };
+
+        let field = &mut def.fields[path[0]];
+        field.kind = match &field.kind {
+            FieldKind::Value(ValueType::F64) => FieldKind::Pointer,
+            FieldKind::Value(t) if t.is_numeric() => FieldKind::Value(t.next()),
+            _ => FieldKind::Value(ValueType::I8),
+        };
+        field.length = None;
+        field.name = format!("{}_{:X}", field.kind.type_name(), field.offset);
+    }
+
+    /// Adds the draft being shown to the definitions file
+    pub fn save_struct_draft(&mut self) {
+        let def = match self.struct_defs.get_mut(self.struct_view.def) {
+            Some(d) if d.draft => d,
+            _ => {
+                self.ui_msg = Some("Nothing to save, a auto dissects".to_string());
+                return;
+            }
+        };
+
+        let path = structs_path();
+        // The file might have gotten a struct of the same name since it was loaded
+        if let Ok(saved) = load_structs(&path) {
+            def.name = unique_name(&saved, &def.name);
         }
+        match append_struct(&path, def) {
+            Ok(_) => {
+                def.draft = false;
+                self.ui_msg = Some(format!(
+                    "Added {} to {}, edit it there and reload with r",
+                    def.name,
+                    path.display()
+                ));
+            }
+            Err(e) => self.ui_msg = Some(format!("Failed to save {}: {}", path.display(), e)),
+        }
+    }
+
+    /// Shows the instance as the next struct definition
+    pub fn cycle_struct_def(&mut self) {
+        if !self.struct_defs.is_empty() {
+            self.struct_view.def = (self.struct_view.def + 1) % self.struct_defs.len();
+            self.struct_view.expanded.clear();
+        }
+    }
+
+    /// Re-reads the struct instance while it's shown, called every tick
+    pub fn refresh_struct_view(&mut self) {
+        if self.sub_screen == SubScreen::StructView {
+            self.struct_view
+                .refresh(&self.task.pid, &self.struct_defs, self.pointer_config.width);
+        }
+    }
+
+    /// Expands or collapses the highlighted field, or edits it if it's a value
+    pub fn select_struct_field(&mut self) {
+        let row = match self.struct_view.selected_row() {
+            Some(r) => r,
+            None => return,
+        };
+
+        let (value_type, endianness, size) = match row.value_type {
+            Some(t) => t,
+            None => return self.struct_view.toggle_selected(),
+        };
+
+        let (start, end) = match row.addr.and_then(|a| Some((a, a.checked_add(size)?))) {
+            Some(range) => range,
+            None => {
+                self.ui_msg = Some("The field is past the end of the address space".to_string());
+                return;
+            }
+        };
+
+        let mut location = SearchLocation::new();
+        location.start = start;
+        location.end = end;
+        location.value_type = value_type;
+        location.endianness = endianness;
+        location.value = row.value.clone();
+        self.selected_value = Some(location);
+        self.sub_screen = SubScreen::ValueEditing;
+    }
+
+    /// Opens the hex view at the highlighted field
+    pub fn struct_field_hex_view(&mut self) {
+        if let Some(addr) = self.struct_view.selected_row().and_then(|r| r.addr) {
+            self.hex_view.jump(addr);
+            self.sub_screen = SubScreen::HexView;
+        }
+    }
 
-        match mem.write_all(new_val) {
+    /// The typed value encoded as the type of the value being edited.
+    ///
+    /// Refuses anything longer than the value, writing it would overwrite what comes after.
+    pub fn new_value_bytes(&self) -> Result<Vec<u8>, String> {
+        let location = match &self.selected_value {
+            Some(l) => l,
+            None => return Err("Invalid memory address".to_string()),
+        };
+        let length = location.end - location.start;
+
+        let mut bytes = location
+            .value_type
+            .encode(&self.new_value, location.endianness)?;
+        let nul = match location.value_type {
+            ValueType::Str(encoding) => Some(encoding.nul()),
+            ValueType::Regex => Some(vec![0]),
+            _ => None,
+        };
+        if let Some(nul) = nul {
+            self.str_fill.apply(&mut bytes, &nul, length);
+        }
+
+        if bytes.is_empty() {
+            return Err("Type a value to write".to_string());
+        }
+        if bytes.len() > length {
+            return Err(format!(
+                "{} bytes don't fit in the {} byte value, they'd overwrite what comes after it",
+                bytes.len(),
+                length
+            ));
+        }
+        Ok(bytes)
+    }
+
+    /// Cycles how strings shorter than the value being edited are written
+    pub fn cycle_str_fill(&mut self) {
+        self.str_fill = self.str_fill.next();
+    }
+
+    pub fn edit_memory(&mut self) {
+        let (start, length) = match &self.selected_value {
+            Some(l) => (l.start, l.end - l.start),
+            None => {
+                self.ui_msg = Some("Invalid memory address".to_string());
+                return;
+            }
+        };
+
+        let bytes = match self.new_value_bytes() {
+            Ok(b) => b,
+            Err(e) => {
+                self.ui_msg = Some(e);
+                return;
+            }
+        };
+
+        match self.write_memory("edit", start, &bytes) {
             Ok(_) => {
-                self.ui_msg = Some("Written!".to_string());
+                self.ui_msg = Some(format!(
+                    "Wrote {} bytes into the {} byte value at {}",
+                    bytes.len(),
+                    length,
+                    format_address(&self.task_mem.maps, start)
+                ));
             }
             Err(e) => {
-                self.ui_msg = Some("Failed to write at memory".to_string());
+                self.ui_msg = Some(e.clone());
                 trace_dbg!(e);
             }
         };
     }
+
+    /// Writes `bytes` at `addr`, keeping what they replace in the journal and the audit log
+    fn write_memory(&mut self, kind: &str, addr: usize, bytes: &[u8]) -> Result<(), String> {
+        check_writable()?;
+        let entry = self.journal.write(&self.task.pid, addr, bytes)?;
+        self.audit(kind, addr, &entry.old, &entry.new, None)
+    }
+
+    /// Appends a write that was made to the audit log
+    fn audit(
+        &self,
+        kind: &str,
+        addr: usize,
+        old: &[u8],
+        new: &[u8],
+        rewrites: Option<u64>,
+    ) -> Result<(), String> {
+        let (uid, sudo_uid) = current_uids();
+        let entry = AuditEntry {
+            time: now(),
+            uid,
+            sudo_uid,
+            pid: self.task.pid.clone(),
+            process: self.task.name.clone(),
+            kind: kind.to_string(),
+            address: format_hex(addr),
+            location: format_address(&self.task_mem.maps, addr),
+            old: bytes_to_hex(old),
+            new: bytes_to_hex(new),
+            rewrites,
+        };
+
+        append_entry(&entry).map_err(|e| {
+            trace_dbg!(&e);
+            format!(
+                "Wrote at {}, but couldn't add it to the audit log: {}",
+                format_hex(addr),
+                e
+            )
+        })
+    }
+
+    /// Undoes the last `count` writes, newest first
+    pub fn undo_writes(&mut self, count: usize) {
+        self.replay_journal(count, true);
+    }
+
+    /// Redoes the last `count` undone writes
+    pub fn redo_writes(&mut self, count: usize) {
+        self.replay_journal(count, false);
+    }
+
+    pub fn undo_all_writes(&mut self) {
+        self.undo_writes(self.journal.done.len().max(1));
+    }
+
+    /// Undoes the highlighted write and the ones after it, or redoes up to it if it's undone
+    pub fn rewind_journal(&mut self) {
+        let idx = match self.journal.table_state.selected() {
+            Some(i) => i,
+            None => return,
+        };
+
+        let (count, undo) = self.journal.rewind_to(idx);
+        self.replay_journal(count, undo);
+    }
+
+    fn replay_journal(&mut self, count: usize, undo: bool) {
+        if let Err(e) = check_writable() {
+            self.ui_msg = Some(e);
+            return;
+        }
+        let verb = if undo { "Undid" } else { "Redid" };
+
+        let mut last = None;
+        let mut audit_error = None;
+        for replayed in 0..count {
+            let result = match undo {
+                true => self.journal.undo(&self.task.pid),
+                false => self.journal.redo(&self.task.pid),
+            };
+            let entry = match result {
+                Ok(entry) => entry,
+                Err(e) if replayed == 0 => {
+                    self.ui_msg = Some(e.clone());
+                    trace_dbg!(e);
+                    return;
+                }
+                Err(e) => {
+                    self.ui_msg = Some(format!("{} {} writes, then: {}", verb, replayed, e));
+                    trace_dbg!(e);
+                    return;
+                }
+            };
+
+            // The write went through either way, a missing log line shouldn't stop the rest
+            let (old, new) = match undo {
+                true => (&entry.new, &entry.old),
+                false => (&entry.old, &entry.new),
+            };
+            let kind = if undo { "undo" } else { "redo" };
+            if let Err(e) = self.audit(kind, entry.addr, old, new, None) {
+                audit_error = Some(e);
+            }
+            last = Some(entry);
+        }
+
+        if let Some(e) = audit_error {
+            self.ui_msg = Some(e);
+            return;
+        }
+
+        self.ui_msg = match (count, last) {
+            (1, Some(entry)) => {
+                let bytes = if undo { &entry.old } else { &entry.new };
+                Some(format!(
+                    "{} the write at {}, it's {} again",
+                    verb,
+                    format_address(&self.task_mem.maps, entry.addr),
+                    bytes_to_hex(bytes)
+                ))
+            }
+            _ => Some(format!("{} {} writes", verb, count)),
+        };
+    }
 }
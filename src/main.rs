@@ -7,19 +7,50 @@ use proc_utils::read_maps;
 use ratatui::{backend::CrosstermBackend, Terminal};
 use ui::render_ui;
 
-use crate::{memory_model::search_mem, utils::num_to_hex};
+use crate::{
+    memory_model::{search_mem, SearchQuery},
+    proc_mem::lock_read_only,
+    scan_worker::ScanControl,
+    utils::num_to_hex,
+    value_type::{Endianness, StrEncoding, ValueType},
+};
 
+mod address;
+mod address_list;
+mod aob;
+mod audit;
 mod components;
 mod event_handler;
+mod expression;
+mod freezer;
+mod hex_view;
+mod journal;
 mod kunai;
 mod logging;
 mod memory_model;
+mod next_scan;
+mod pointer_scan;
+mod proc_mem;
 mod proc_utils;
+mod scan_pool;
+mod scan_worker;
+mod snapshot;
+mod struct_guess;
+mod structs;
+mod symbols;
 mod tui;
 mod ui;
 mod utils;
+mod value_type;
 
 fn main() -> Result<(), Box<dyn Error>> {
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "--read-only" => lock_read_only(),
+            _ => return Err(format!("Unknown argument '{}', try --read-only", arg).into()),
+        }
+    }
+
     initialize_logging()?;
     let mut kunai = Kunai::new();
 
@@ -35,7 +66,14 @@ fn main() -> Result<(), Box<dyn Error>> {
 fn dummy_runner(kunai: &mut Kunai) {
     let pid = "11489".to_string();
     let maps = read_maps(&pid).unwrap();
-    let search_string = "SomeString".to_string();
+    let query = SearchQuery::new(
+        "SomeString",
+        ValueType::Str(StrEncoding::Utf8),
+        Endianness::Little,
+        1,
+    )
+    .unwrap();
+    let (control, _progress) = ScanControl::new();
 
     for map in &maps {
         println!(
@@ -45,7 +83,7 @@ fn dummy_runner(kunai: &mut Kunai) {
             num_to_hex(map.end as i64),
             map.perms
         );
-        let res = search_mem(&pid, &search_string, map).unwrap();
+        let res = search_mem(&pid, &query, map, &control).unwrap();
         println!("{:?}", res);
     }
 }
@@ -53,11 +91,17 @@ fn dummy_runner(kunai: &mut Kunai) {
 fn run_app(terminal: &mut Terminal<CrosstermBackend<Stdout>>, kunai: &mut Kunai) {
     kunai.tasks.refresh_list();
     loop {
+        kunai.memedit.poll_scan();
+        kunai.memedit.poll_freezer();
+        kunai.memedit.refresh_address_list();
+        kunai.memedit.refresh_hex_view();
+        kunai.memedit.refresh_struct_view();
         terminal.draw(|f| render_ui(f, kunai)).unwrap(); // TODO: Error handling
 
-        // TODO: Background thread?
         if !handle_keypress(kunai) {
             break;
         }
     }
+
+    kunai.memedit.audit_freeze_rewrites();
 }
@@ -1,12 +1,17 @@
-use std::{
-    fs::File,
-    io::{self, Read, Seek, SeekFrom},
-    usize,
-};
+use std::{fs::File, io, os::unix::fs::FileExt};
 
 use memchr::memmem;
-
-use crate::{proc_utils::read_maps, trace_dbg, utils::bytes_to_string};
+use regex::bytes::{Regex, RegexBuilder};
+
+use crate::{
+    aob::Pattern,
+    proc_utils::read_maps,
+    scan_pool::run_jobs,
+    scan_worker::ScanControl,
+    trace_dbg,
+    utils::glob_match,
+    value_type::{Endianness, StrEncoding, ValueType},
+};
 
 /// TODO: Do I require this struct?
 #[derive(Debug)]
@@ -20,11 +25,71 @@ impl TaskMemory {
         TaskMemory { maps: Vec::new() }
     }
 
-    pub fn populate_info(&mut self, pid: &String) {
-        self.maps = match read_maps(pid) {
-            Ok(m) => m,
-            Err(_) => Vec::new(), // TODO: Error handling here!
-        };
+    pub fn populate_info(&mut self, pid: &str) {
+        self.maps = read_maps(pid).unwrap_or_default(); // TODO: Error handling here!
+    }
+
+    pub fn toggle_map(&mut self, index: usize) {
+        if let Some(map) = self.maps.get_mut(index) {
+            map.should_search = !map.should_search;
+        }
+    }
+
+    pub fn apply_preset(&mut self, preset: &MapPreset) {
+        for map in &mut self.maps {
+            map.should_search = preset.select(map);
+        }
+    }
+
+    pub fn selected_count(&self) -> usize {
+        self.maps.iter().filter(|m| m.should_search).count()
+    }
+
+    /// Whether the map an address was found in is still selected.
+    /// Addresses in maps we don't know about anymore are kept.
+    pub fn is_selected(&self, map: &MemoryMap) -> bool {
+        self.maps
+            .iter()
+            .find(|m| m.start == map.start)
+            .is_none_or(|m| m.should_search)
+    }
+}
+
+/// Bulk selections for the maps popup
+#[derive(Debug, Clone)]
+pub enum MapPreset {
+    All,
+    None,
+    /// Only maps with exactly these perms, e.g. `rw-p`
+    Perms(String),
+    /// Deselects maps backed by a file, keeps the rest as is
+    ExcludeFileBacked,
+    /// Only `[heap]`, `[stack]` and anonymous maps
+    HeapStackAnon,
+    /// Only the maps of the module with this name
+    Module(String),
+    /// Only maps with a name matching the glob
+    Glob(String),
+}
+
+impl MapPreset {
+    /// The new selection state of a map
+    fn select(&self, map: &MemoryMap) -> bool {
+        match self {
+            MapPreset::All => true,
+            MapPreset::None => false,
+            MapPreset::Perms(perms) => map.perms == *perms,
+            MapPreset::ExcludeFileBacked => map.should_search && !map.name.starts_with('/'),
+            MapPreset::HeapStackAnon => {
+                // `read_maps` names anonymous maps `-`
+                map.name == "-"
+                    || map.name == "[heap]"
+                    || map.name.starts_with("[stack")
+                    || map.name.starts_with("[anon")
+            }
+            MapPreset::Module(name) => map.name == *name,
+            MapPreset::Glob(glob) => glob_match(glob, &map.name),
+        }
     }
 }
 
@@ -38,6 +103,9 @@ pub struct MemoryMap {
     pub end: usize,
     pub perms: String,
     pub name: String,
+    /// Lowest start of the file this map belongs to, set for file backed maps.
+    /// Module relative addresses are offsets from it.
+    pub base: Option<usize>,
 
     // UI stuff
     pub should_search: bool, // Deafult true
@@ -50,6 +118,7 @@ impl MemoryMap {
             end: 0,
             perms: String::new(),
             name: String::new(),
+            base: None,
             should_search: true,
         }
     }
@@ -62,7 +131,12 @@ pub struct SearchLocation {
     pub value: String,
     pub prev_value: String,
     pub mem_info: MemoryMap,
-    // TODO: prev value
+    pub value_type: ValueType,
+    pub endianness: Endianness,
+
+    // Raw bytes, used for comparisons by next scans
+    pub bytes: Vec<u8>,
+    pub first_bytes: Vec<u8>,
 }
 
 impl SearchLocation {
@@ -73,95 +147,449 @@ impl SearchLocation {
             value: String::new(),
             prev_value: String::new(),
             mem_info: MemoryMap::new(), // TODO: This is bad
+            value_type: ValueType::Str(StrEncoding::Utf8),
+            endianness: Endianness::Little,
+            bytes: Vec::new(),
+            first_bytes: Vec::new(),
         }
     }
+
+    /// Decodes the bytes read at this location according to its type
+    pub fn decode(&self, bytes: &[u8]) -> String {
+        self.value_type.decode(bytes, self.endianness)
+    }
+
+    /// Stores freshly read bytes, shifting the current value into `prev_value`
+    pub fn update(&mut self, bytes: Vec<u8>) {
+        let new_value = self.decode(&bytes);
+        self.prev_value = std::mem::replace(&mut self.value, new_value);
+        self.bytes = bytes;
+    }
+}
+
+/// The encoded value `search_mem` looks for
+#[derive(Debug, Clone)]
+pub struct SearchQuery {
+    pub needle: Vec<u8>,
+    pub value_type: ValueType,
+    pub endianness: Endianness,
+    pub alignment: usize,
+    /// Set for AOB searches, `needle` then holds the pattern bytes
+    pub pattern: Option<Pattern>,
+    /// Set for regex searches, matches can have any length
+    pub regex: Option<Regex>,
+    /// Only scan maps with `x` perms (used for AOB code signatures)
+    pub executable_only: bool,
 }
 
-pub fn read_mem(pid: &String, start: usize, end: usize) -> io::Result<Vec<u8>> {
+impl SearchQuery {
+    pub fn new(
+        input: &str,
+        value_type: ValueType,
+        endianness: Endianness,
+        alignment: usize,
+    ) -> Result<SearchQuery, String> {
+        let pattern = match value_type {
+            ValueType::Aob => Some(Pattern::parse(input)?),
+            _ => None,
+        };
+
+        let regex = match value_type {
+            // Memory isn't UTF-8, so `.` and `\xNN` should match single bytes
+            ValueType::Regex => match RegexBuilder::new(input).unicode(false).build() {
+                Ok(r) => Some(r),
+                Err(e) => return Err(format!("Invalid regex: {}", e)),
+            },
+            _ => None,
+        };
+
+        let needle = match &pattern {
+            Some(p) => p.bytes.clone(),
+            None => value_type.encode(input, endianness)?,
+        };
+        if needle.is_empty() {
+            return Err("Nothing to search for".to_string());
+        }
+
+        Ok(SearchQuery {
+            needle,
+            value_type,
+            endianness,
+            alignment: alignment.max(1),
+            pattern,
+            regex,
+            executable_only: false,
+        })
+    }
+
+    /// Turns a string search into a regex matching every case variant of the string
+    pub fn ignore_case(mut self, input: &str) -> Result<SearchQuery, String> {
+        let encoding = match self.value_type {
+            ValueType::Str(e) => e,
+            _ => return Ok(self),
+        };
+        if input.is_empty() {
+            return Err("Nothing to search for".to_string());
+        }
+
+        let regex = match encoding {
+            // The regex crate knows the Unicode case rules, but only for UTF-8
+            StrEncoding::Utf8 => RegexBuilder::new(&format!("(?i){}", regex::escape(input)))
+                .unicode(true)
+                .build(),
+            _ => RegexBuilder::new(&case_variants(input, encoding)?)
+                .unicode(false)
+                .build(),
+        };
+
+        match regex {
+            Ok(r) => self.regex = Some(r),
+            Err(e) => return Err(format!("Invalid search string: {}", e)),
+        }
+
+        Ok(self)
+    }
+
+    /// (offset, length) of every match in the haystack
+    pub fn find_all(&self, haystack: &[u8]) -> Vec<(usize, usize)> {
+        match (&self.pattern, &self.regex) {
+            (Some(pattern), _) => pattern
+                .find_all(haystack)
+                .into_iter()
+                .map(|o| (o, self.needle.len()))
+                .collect(),
+            (_, Some(regex)) => regex
+                .find_iter(haystack)
+                .filter(|m| !m.is_empty())
+                .map(|m| (m.start(), m.len()))
+                .collect(),
+            // `find_iter` skips matches overlapping the previous one, numbers can overlap
+            // (e.g. u16 257 twice in `01 01 01`)
+            _ if self.value_type.is_numeric() => {
+                let finder = memmem::Finder::new(&self.needle);
+                let mut found = Vec::new();
+                let mut pos = 0;
+                while let Some(o) = finder.find(&haystack[pos..]) {
+                    found.push((pos + o, self.needle.len()));
+                    pos += o + 1;
+                }
+                found
+            }
+            _ => memmem::find_iter(haystack, &self.needle)
+                .map(|o| (o, self.needle.len()))
+                .collect(),
+        }
+    }
+
+    /// How far a match can reach past the byte it starts at
+    pub fn overlap(&self) -> usize {
+        match self.regex {
+            Some(_) => REGEX_OVERLAP,
+            None => self.needle.len() - 1,
+        }
+    }
+
+    pub fn should_scan(&self, map: &MemoryMap) -> bool {
+        map.should_search && (!self.executable_only || map.perms.contains('x'))
+    }
+}
+
+/// A byte pattern matching every case variant of each char, as `encoding` stores it
+fn case_variants(input: &str, encoding: StrEncoding) -> Result<String, String> {
+    let mut pattern = String::new();
+    for c in input.chars() {
+        let mut variants: Vec<String> = c
+            .to_lowercase()
+            .chain(c.to_uppercase())
+            .chain([c])
+            .map(|v| v.to_string())
+            .collect();
+        variants.sort();
+        variants.dedup();
+
+        let mut alternatives: Vec<String> = Vec::new();
+        for v in variants {
+            // Chars without a representation in this encoding can't match
+            if let Ok(bytes) = encoding.encode(&v) {
+                alternatives.push(bytes.iter().map(|b| format!("\\x{:02x}", b)).collect());
+            }
+        }
+        if alternatives.is_empty() {
+            return Err(format!("'{}' can't be encoded as {}", c, encoding));
+        }
+        pattern += &format!("(?:{})", alternatives.join("|"));
+    }
+    Ok(pattern)
+}
+
+/// Maps are read in chunks of this size instead of all at once
+pub const CHUNK_SIZE: usize = 4 * 1024 * 1024;
+
+/// Unreadable memory is skipped a page at a time
+pub const PAGE_SIZE: usize = 4096;
+
+/// Regex matches longer than this might be missed when they cross a chunk boundary
+pub const REGEX_OVERLAP: usize = 4096;
+
+/// Streams a map through `f` one chunk at a time.
+///
+/// `f` gets the address of the chunk, the chunk and how many bytes of it it owns.
+/// The bytes after the owned part overlap with the next chunk (at most `overlap` of them),
+/// so matches starting in the owned part are found even when they cross into the next chunk.
+/// Unreadable pages are skipped instead of giving up on the whole map.
+pub fn for_each_chunk<F>(pid: &str, map: &MemoryMap, overlap: usize, mut f: F) -> io::Result<()>
+where
+    F: FnMut(usize, &[u8], usize) -> io::Result<()>,
+{
     let mem_file = "/proc/".to_string() + pid + "/mem";
-    let mut mem = match File::open(mem_file) {
-        Ok(f) => f,
-        Err(e) => return Err(e),
-    };
-    trace_dbg!(start);
-    trace_dbg!(end);
+    let mem = File::open(mem_file)?;
+
+    let mut buf = Vec::with_capacity(CHUNK_SIZE + overlap);
+    let mut addr = map.start;
 
-    let read_len = start.abs_diff(end); // |start - end| (Mathematical expression)
+    while addr < map.end {
+        let want = (CHUNK_SIZE + overlap).min(map.end - addr);
+        read_readable(&mem, addr, want, &mut buf);
 
-    trace_dbg!(read_len);
+        if buf.is_empty() {
+            // Skip the unreadable page
+            addr = (addr / PAGE_SIZE + 1) * PAGE_SIZE;
+            continue;
+        }
+
+        // A short read stopped at an unreadable page, nothing to overlap with
+        let owned = if buf.len() == want && addr + want < map.end {
+            CHUNK_SIZE
+        } else {
+            buf.len()
+        };
 
-    let mut mem_buf = vec![0u8; read_len];
+        f(addr, &buf, owned)?;
+        addr += owned;
+    }
 
-    mem.seek(SeekFrom::Start(start as u64))?;
+    Ok(())
+}
+
+/// Reads up to `len` bytes at `addr`, stopping at the first unreadable page
+fn read_readable(mem: &File, addr: usize, len: usize, buf: &mut Vec<u8>) {
+    buf.resize(len, 0);
+    if mem.read_exact_at(buf, addr as u64).is_ok() {
+        return;
+    }
 
-    mem.read_exact(&mut mem_buf)?;
+    // Fall back to page sized reads to find where the readable part ends
+    let mut read = 0;
+    while read < len {
+        let page_end = ((addr + read) / PAGE_SIZE + 1) * PAGE_SIZE;
+        let n = (page_end - (addr + read)).min(len - read);
 
-    Ok(mem_buf)
+        if let Err(e) = mem.read_exact_at(&mut buf[read..read + n], (addr + read) as u64) {
+            trace_dbg!(e);
+            break;
+        }
+        read += n;
+    }
+
+    buf.truncate(read);
 }
 
 /// Searches a single map of memory
 pub fn search_mem(
-    pid: &String,
-    search_string: &String,
+    pid: &str,
+    query: &SearchQuery,
     map: &MemoryMap,
+    control: &ScanControl,
 ) -> io::Result<Vec<SearchLocation>> {
-    let mut locs = Vec::new();
+    let mut locs: Vec<SearchLocation> = Vec::new();
 
-    // open mem file
-    let mem_file = "/proc/".to_string() + pid + "/mem";
-    let mut mem = match File::open(mem_file) {
-        Ok(f) => f,
-        Err(e) => return Err(e),
-    };
+    for_each_chunk(pid, map, query.overlap(), |addr, chunk, owned| {
+        control.check()?;
+        let found = locs.len();
 
-    // Convert the search string to bytes
-    let search_bytes = search_string.as_bytes();
+        for (occurance, len) in query.find_all(chunk) {
+            // Starts in the overlap, the next chunk reports it
+            if occurance >= owned {
+                continue;
+            }
 
-    let mem_start = map.start;
-    let mem_end = map.end;
+            // The tail of a regex match reaching into this chunk, it was reported already
+            if query.regex.is_some() && locs.last().is_some_and(|l| addr + occurance < l.end) {
+                continue;
+            }
 
-    mem.seek(SeekFrom::Start(mem_start as u64))?;
+            // Typed values are usually stored at aligned addresses
+            if !(addr + occurance).is_multiple_of(query.alignment) {
+                continue;
+            }
 
-    let mem_size = mem_end - mem_start;
-    trace_dbg!(mem_size);
+            let mut loc = SearchLocation::new();
 
-    let mut mem_buf = vec![0u8; mem_size];
+            loc.start = addr + occurance;
+            loc.end = addr + occurance + len;
+            loc.mem_info = map.clone();
+            loc.value_type = query.value_type;
+            loc.endianness = query.endianness;
 
-    mem.read_exact(&mut mem_buf)?;
+            // The value is already in the buffer, no need to read it again
+            loc.bytes = chunk[occurance..occurance + len].to_vec();
+            loc.first_bytes = loc.bytes.clone();
+            loc.value = loc.decode(&loc.bytes);
 
-    let it = memmem::find_iter(&mem_buf, &search_bytes);
+            trace_dbg!(&loc);
 
-    for occurance in it {
-        // println!("Found occurance in {} at {}", map.name, occurance);
-        let mut loc = SearchLocation::new();
+            locs.push(loc);
+        }
 
-        loc.start = map.start + occurance;
-        loc.end = map.start + occurance + search_bytes.len();
-        trace_dbg!(loc.end);
-        loc.mem_info = map.clone();
+        control.scanned(owned, locs.len() - found);
+        Ok(())
+    })?;
 
-        match mem.seek(SeekFrom::Start(loc.start as u64)) {
-            Ok(_) => {}
-            Err(e) => {
-                trace_dbg!(e);
-                continue;
-            }
+    Ok(locs)
+}
+
+/// Maps bigger than this are split into several scan jobs
+pub const JOB_SIZE: usize = 64 * 1024 * 1024;
+
+/// Searches every map the query wants on `threads` workers.
+/// Big maps are split up so a single huge heap doesn't keep one worker busy.
+/// The locations are returned in address order.
+pub fn search_maps(
+    pid: &str,
+    query: &SearchQuery,
+    maps: &[MemoryMap],
+    threads: usize,
+    control: &ScanControl,
+) -> Vec<SearchLocation> {
+    let mut jobs = Vec::new();
+    for map in maps.iter().filter(|m| query.should_scan(m)) {
+        let mut start = map.start;
+        while start < map.end {
+            let end = (start + JOB_SIZE).min(map.end);
+            jobs.push((map, start, end));
+            start = end;
         }
-        let mut value = vec![0u8; search_bytes.len()];
-        match mem.read_exact(&mut value) {
-            Ok(_) => {}
+    }
+
+    let results = run_jobs(&jobs, threads, |(map, start, end)| {
+        // Read a bit into the next job so matches crossing into it are found
+        let mut part = (*map).clone();
+        part.start = *start;
+        part.end = (*end + query.overlap()).min(map.end);
+
+        let res = search_mem(pid, query, &part, control).map(|locs| {
+            locs.into_iter()
+                .filter(|l| l.start < *end)
+                .map(|mut l| {
+                    l.mem_info = (*map).clone();
+                    l
+                })
+                .collect::<Vec<SearchLocation>>()
+        });
+
+        control.map_done();
+        res
+    });
+
+    let mut locations: Vec<SearchLocation> = Vec::new();
+    for res in results {
+        match res {
+            Ok(locs) => {
+                for loc in locs {
+                    // The tail of a regex match reaching into the next job, it was reported already
+                    if query.regex.is_some()
+                        && locations.last().is_some_and(|l| {
+                            l.mem_info.start == loc.mem_info.start && loc.start < l.end
+                        })
+                    {
+                        continue;
+                    }
+                    locations.push(loc);
+                }
+            }
             Err(e) => {
                 trace_dbg!(e);
-                continue;
             }
         }
+    }
+
+    locations
+}
 
-        loc.value = bytes_to_string(value);
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A map covering `buf` in the test process
+    fn map_of(buf: &[u8]) -> MemoryMap {
+        let mut map = MemoryMap::new();
+        map.start = buf.as_ptr() as usize;
+        map.end = map.start + buf.len();
+        map.perms = "rw-p".to_string();
+        map
+    }
+
+    #[test]
+    fn regex_match_across_jobs_is_reported_once() {
+        let mut buf = vec![0u8; JOB_SIZE + 4096];
+        buf[JOB_SIZE - 10..JOB_SIZE + 20].fill(b'a');
+        let map = map_of(&buf);
 
-        trace_dbg!(&loc);
+        let pid = std::process::id().to_string();
+        let query = SearchQuery::new("a+", ValueType::Regex, Endianness::Little, 1).unwrap();
+        let (control, _rx) = ScanControl::new();
+        let locs = search_maps(&pid, &query, std::slice::from_ref(&map), 2, &control);
 
-        locs.push(loc);
+        let found: Vec<(usize, usize)> = locs.iter().map(|l| (l.start, l.end - l.start)).collect();
+        assert_eq!(found, vec![(map.start + JOB_SIZE - 10, 30)]);
     }
 
-    Ok(locs)
+    #[test]
+    fn numeric_matches_overlap() {
+        let query = SearchQuery::new("257", ValueType::U16, Endianness::Little, 1).unwrap();
+        assert_eq!(query.find_all(&[1, 1, 1, 1]), vec![(0, 2), (1, 2), (2, 2)]);
+    }
+
+    #[test]
+    fn match_across_chunks_is_found_once() {
+        let mut buf = vec![0u8; 2 * CHUNK_SIZE + 100];
+        buf[CHUNK_SIZE - 3..CHUNK_SIZE + 3].copy_from_slice(b"kunai!");
+        let map = map_of(&buf);
+        let pid = std::process::id().to_string();
+
+        let mut chunks = Vec::new();
+        let mut found = Vec::new();
+        for_each_chunk(&pid, &map, 5, |addr, chunk, owned| {
+            chunks.push((addr - map.start, chunk.len(), owned));
+            found.extend(
+                memmem::find_iter(chunk, b"kunai!")
+                    .filter(|o| *o < owned)
+                    .map(|o| addr + o - map.start),
+            );
+            Ok(())
+        })
+        .unwrap();
+
+        assert_eq!(
+            chunks,
+            [
+                (0, CHUNK_SIZE + 5, CHUNK_SIZE),
+                (CHUNK_SIZE, CHUNK_SIZE + 5, CHUNK_SIZE),
+                (2 * CHUNK_SIZE, 100, 100),
+            ]
+        );
+        assert_eq!(found, [CHUNK_SIZE - 3]);
+
+        let query = SearchQuery::new(
+            "kunai!",
+            ValueType::Str(StrEncoding::Utf8),
+            Endianness::Little,
+            1,
+        )
+        .unwrap();
+        let (control, _rx) = ScanControl::new();
+        let locs = search_mem(&pid, &query, &map, &control).unwrap();
+        let starts: Vec<usize> = locs.iter().map(|l| l.start - map.start).collect();
+        assert_eq!(starts, [CHUNK_SIZE - 3]);
+    }
 }
@@ -0,0 +1,281 @@
+use std::fmt;
+
+use crate::value_type::{Endianness, Number, ValueType};
+
+/// How a re-read value is compared when narrowing down the search list
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompareMode {
+    Equal,
+    NotEqual,
+    Greater,
+    Less,
+    Changed,
+    Unchanged,
+    Increased,
+    Decreased,
+    IncreasedBy,
+    DecreasedBy,
+}
+
+/// Which older value `Changed`, `Increased`, ... compare against
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompareAgainst {
+    Previous,
+    Original,
+}
+
+/// A parsed next scan, ready to be matched against every location
+#[derive(Debug, Clone)]
+pub struct NextScan {
+    pub mode: CompareMode,
+    pub against: CompareAgainst,
+    value_type: ValueType,
+    endianness: Endianness,
+    operand: Option<Operand>,
+}
+
+#[derive(Debug, Clone)]
+enum Operand {
+    Bytes(Vec<u8>),
+    Number(Number),
+}
+
+impl CompareMode {
+    pub fn next(self) -> CompareMode {
+        match self {
+            CompareMode::Equal => CompareMode::NotEqual,
+            CompareMode::NotEqual => CompareMode::Greater,
+            CompareMode::Greater => CompareMode::Less,
+            CompareMode::Less => CompareMode::Changed,
+            CompareMode::Changed => CompareMode::Unchanged,
+            CompareMode::Unchanged => CompareMode::Increased,
+            CompareMode::Increased => CompareMode::Decreased,
+            CompareMode::Decreased => CompareMode::IncreasedBy,
+            CompareMode::IncreasedBy => CompareMode::DecreasedBy,
+            CompareMode::DecreasedBy => CompareMode::Equal,
+        }
+    }
+
+    /// Whether the search string is used as an operand
+    pub fn needs_input(self) -> bool {
+        matches!(
+            self,
+            CompareMode::Equal
+                | CompareMode::NotEqual
+                | CompareMode::Greater
+                | CompareMode::Less
+                | CompareMode::IncreasedBy
+                | CompareMode::DecreasedBy
+        )
+    }
+
+    /// Whether the comparison only makes sense for numbers
+    fn is_numeric(self) -> bool {
+        !matches!(
+            self,
+            CompareMode::Equal
+                | CompareMode::NotEqual
+                | CompareMode::Changed
+                | CompareMode::Unchanged
+        )
+    }
+}
+
+impl fmt::Display for CompareMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            CompareMode::Equal => "equal",
+            CompareMode::NotEqual => "not equal",
+            CompareMode::Greater => "greater",
+            CompareMode::Less => "less",
+            CompareMode::Changed => "changed",
+            CompareMode::Unchanged => "unchanged",
+            CompareMode::Increased => "increased",
+            CompareMode::Decreased => "decreased",
+            CompareMode::IncreasedBy => "increased by",
+            CompareMode::DecreasedBy => "decreased by",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl CompareAgainst {
+    pub fn toggle(self) -> CompareAgainst {
+        match self {
+            CompareAgainst::Previous => CompareAgainst::Original,
+            CompareAgainst::Original => CompareAgainst::Previous,
+        }
+    }
+}
+
+impl fmt::Display for CompareAgainst {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CompareAgainst::Previous => write!(f, "previous"),
+            CompareAgainst::Original => write!(f, "original"),
+        }
+    }
+}
+
+impl NextScan {
+    pub fn new(
+        mode: CompareMode,
+        against: CompareAgainst,
+        input: &str,
+        value_type: ValueType,
+        endianness: Endianness,
+    ) -> Result<NextScan, String> {
+        if mode.is_numeric() && !value_type.is_numeric() {
+            return Err(format!(
+                "Can't scan for '{}' on {} values",
+                mode, value_type
+            ));
+        }
+
+        let operand = if !mode.needs_input() {
+            None
+        } else if value_type.is_numeric() {
+            Some(Operand::Number(value_type.parse_number(input)?))
+        } else {
+            Some(Operand::Bytes(value_type.encode(input, endianness)?))
+        };
+
+        Ok(NextScan {
+            mode,
+            against,
+            value_type,
+            endianness,
+            operand,
+        })
+    }
+
+    /// Checks the freshly read `current` bytes against the operand or `old` bytes
+    pub fn matches(&self, current: &[u8], old: &[u8]) -> bool {
+        let number = |b: &[u8]| self.value_type.number(b, self.endianness);
+
+        match (self.mode, &self.operand) {
+            (CompareMode::Changed, _) => current != old,
+            (CompareMode::Unchanged, _) => current == old,
+            (CompareMode::Equal, Some(Operand::Bytes(b))) => current == b.as_slice(),
+            (CompareMode::NotEqual, Some(Operand::Bytes(b))) => current != b.as_slice(),
+            (mode, operand) => {
+                let Some(cur) = number(current) else {
+                    return false;
+                };
+                let old = number(old);
+                let operand = match operand {
+                    Some(Operand::Number(n)) => Some(*n),
+                    _ => None,
+                };
+
+                match (mode, operand, old) {
+                    (CompareMode::Equal, Some(n), _) => cur.approx_eq(n),
+                    (CompareMode::NotEqual, Some(n), _) => !cur.approx_eq(n),
+                    (CompareMode::Greater, Some(n), _) => cur > n,
+                    (CompareMode::Less, Some(n), _) => cur < n,
+                    (CompareMode::Increased, _, Some(old)) => cur > old,
+                    (CompareMode::Decreased, _, Some(old)) => cur < old,
+                    (CompareMode::IncreasedBy, Some(n), Some(old)) => {
+                        cur.delta(old).is_some_and(|d| d.approx_eq(n))
+                    }
+                    (CompareMode::DecreasedBy, Some(n), Some(old)) => {
+                        old.delta(cur).is_some_and(|d| d.approx_eq(n))
+                    }
+                    _ => false,
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scan(mode: CompareMode, input: &str) -> NextScan {
+        NextScan::new(
+            mode,
+            CompareAgainst::Previous,
+            input,
+            ValueType::I32,
+            Endianness::Little,
+        )
+        .unwrap()
+    }
+
+    fn le(v: i32) -> [u8; 4] {
+        v.to_le_bytes()
+    }
+
+    #[test]
+    fn changed() {
+        let s = scan(CompareMode::Changed, "");
+        assert!(s.matches(&le(5), &le(4)));
+        assert!(!s.matches(&le(5), &le(5)));
+    }
+
+    #[test]
+    fn unchanged() {
+        let s = scan(CompareMode::Unchanged, "");
+        assert!(s.matches(&le(5), &le(5)));
+        assert!(!s.matches(&le(5), &le(4)));
+    }
+
+    #[test]
+    fn increased() {
+        let s = scan(CompareMode::Increased, "");
+        assert!(s.matches(&le(5), &le(-1)));
+        assert!(!s.matches(&le(5), &le(5)));
+        assert!(!s.matches(&le(4), &le(5)));
+    }
+
+    #[test]
+    fn decreased() {
+        let s = scan(CompareMode::Decreased, "");
+        assert!(s.matches(&le(-1), &le(5)));
+        assert!(!s.matches(&le(5), &le(5)));
+        assert!(!s.matches(&le(5), &le(4)));
+    }
+
+    #[test]
+    fn by_value() {
+        let s = scan(CompareMode::Equal, "7");
+        assert!(s.matches(&le(7), &le(0)));
+        assert!(!s.matches(&le(8), &le(7)));
+
+        let s = scan(CompareMode::NotEqual, "7");
+        assert!(s.matches(&le(8), &le(7)));
+        assert!(!s.matches(&le(7), &le(0)));
+
+        let s = scan(CompareMode::Greater, "7");
+        assert!(s.matches(&le(8), &le(0)));
+        assert!(!s.matches(&le(7), &le(0)));
+
+        let s = scan(CompareMode::Less, "7");
+        assert!(s.matches(&le(-8), &le(0)));
+        assert!(!s.matches(&le(7), &le(0)));
+
+        let s = scan(CompareMode::IncreasedBy, "3");
+        assert!(s.matches(&le(1), &le(-2)));
+        assert!(!s.matches(&le(1), &le(-1)));
+
+        let s = scan(CompareMode::DecreasedBy, "3");
+        assert!(s.matches(&le(-2), &le(1)));
+        assert!(!s.matches(&le(1), &le(-2)));
+    }
+
+    #[test]
+    fn numeric_modes_need_numbers() {
+        let new = |mode| {
+            NextScan::new(
+                mode,
+                CompareAgainst::Previous,
+                "a",
+                ValueType::Str(crate::value_type::StrEncoding::Utf8),
+                Endianness::Little,
+            )
+        };
+        assert!(new(CompareMode::Increased).is_err());
+        assert!(new(CompareMode::Equal).is_ok());
+    }
+}
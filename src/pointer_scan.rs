@@ -0,0 +1,406 @@
+use std::{
+    collections::HashSet,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    address::parse_hex,
+    expression::quote_name,
+    logging::get_data_dir,
+    memory_model::{for_each_chunk, MemoryMap},
+    proc_mem::read_batch,
+    scan_pool::run_jobs,
+    scan_worker::ScanControl,
+    trace_dbg,
+};
+
+/// Stop looking once this many chains were found
+pub const MAX_CHAINS: usize = 10_000;
+
+/// Addresses followed per level, keeps deep scans from blowing up
+const MAX_LEVEL_NODES: usize = 1_000_000;
+
+/// How far back a pointer scan walks
+#[derive(Debug, Clone, Copy)]
+pub struct PointerScanConfig {
+    /// Most pointers followed from the module to the target
+    pub max_depth: usize,
+    /// Largest offset added to a pointer (field offset into a struct)
+    pub max_offset: usize,
+    /// Pointer size of the target, 4 or 8
+    pub width: usize,
+}
+
+/// A path from a static address in a module to the target,
+/// e.g. `libgame.so+0x1A2B30 -> +0x18 -> +0x240`
+///
+/// The pointer at `module + module_offset` is read, the first offset added,
+/// the pointer there read and so on. Adding the last offset gives the target.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PointerChain {
+    /// Path of the module, as in `/proc/<pid>/maps`
+    pub module: String,
+    pub module_offset: usize,
+    pub offsets: Vec<usize>,
+}
+
+impl PointerChain {
+    /// Parses the `Display` format, `module+0xOFFSET -> +0xOFFSET -> ...`
+    pub fn parse(input: &str) -> Result<PointerChain, String> {
+        let mut parts = input.split("->").map(|p| p.trim());
+
+        let start = parts.next().unwrap_or_default();
+        let (module, module_offset) = match start.rsplit_once('+') {
+            Some((m, o)) if !m.is_empty() => (m.to_string(), parse_hex(o)?),
+            _ => return Err(format!("'{}' is not module+offset", start)),
+        };
+
+        let mut offsets = Vec::new();
+        for part in parts {
+            match part.strip_prefix('+') {
+                Some(o) => offsets.push(parse_hex(o)?),
+                None => return Err(format!("'{}' is not an offset", part)),
+            }
+        }
+
+        if offsets.is_empty() {
+            return Err("A chain needs at least one offset".to_string());
+        }
+
+        Ok(PointerChain {
+            module,
+            module_offset,
+            offsets,
+        })
+    }
+
+    /// The chain as an address expression, e.g. `[[libgame.so+0x1A2B30]+0x18]+0x240`.
+    /// The module goes by its path when another loaded module has the same file name.
+    pub fn expression(&self, maps: &[MemoryMap]) -> String {
+        let module = file_name(&self.module);
+        let module = match maps
+            .iter()
+            .any(|m| m.name != self.module && file_name(&m.name) == module)
+        {
+            true => &self.module,
+            false => module,
+        };
+        let mut expr = format!("{}+0x{:X}", quote_name(module), self.module_offset);
+        for offset in &self.offsets {
+            expr = format!("[{}]+0x{:X}", expr, offset);
+        }
+        expr
+    }
+
+    /// Follows the chain in the running process, returning the address it ends at
+    pub fn resolve(&self, pid: &str, maps: &[MemoryMap], width: usize) -> Option<usize> {
+        let mut addr = module_base(maps, &self.module)? + self.module_offset;
+
+        for offset in &self.offsets {
+            addr = read_pointer(pid, addr, width)?.wrapping_add(*offset);
+        }
+
+        Some(addr)
+    }
+}
+
+/// `{:#}` keeps the full module path, for saving
+impl fmt::Display for PointerChain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let module = match f.alternate() {
+            true => &self.module,
+            false => file_name(&self.module),
+        };
+        write!(f, "{}+0x{:X}", module, self.module_offset)?;
+        for offset in &self.offsets {
+            write!(f, " -> +0x{:X}", offset)?;
+        }
+        Ok(())
+    }
+}
+
+/// Where a module is loaded, its lowest mapped address.
+/// `module` is either the full path or just the file name.
+pub fn module_base(maps: &[MemoryMap], module: &str) -> Option<usize> {
+    maps.iter()
+        .find(|m| m.name == module || m.name.rsplit('/').next() == Some(module))
+        .and_then(|m| m.base)
+}
+
+pub fn read_pointer(pid: &str, addr: usize, width: usize) -> Option<usize> {
+    match read_batch(pid, &[(addr, addr + width)]).pop()? {
+        Ok(bytes) => Some(pointer_from(&bytes)),
+        Err(e) => {
+            trace_dbg!(e);
+            None
+        }
+    }
+}
+
+/// Where the chains of a process are saved, one file per process name
+pub fn pointer_map_path(process: &str) -> PathBuf {
+    get_data_dir()
+        .join("pointers")
+        .join(format!("{}.ptrs", process))
+}
+
+/// Writes the chains to a pointer map file, one chain per line.
+/// Modules are saved by path, two of them can have the same file name.
+pub fn save_chains(path: &Path, chains: &[PointerChain]) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    let lines: Vec<String> = chains.iter().map(|c| format!("{:#}", c)).collect();
+    fs::write(path, lines.join("\n") + "\n")
+}
+
+/// Reads a pointer map file, skipping lines that aren't chains
+pub fn load_chains(path: &Path) -> io::Result<Vec<PointerChain>> {
+    let mut chains = Vec::new();
+
+    for line in fs::read_to_string(path)?.lines() {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        match PointerChain::parse(line) {
+            Ok(c) => chains.push(c),
+            Err(e) => {
+                trace_dbg!(e);
+            }
+        }
+    }
+
+    Ok(chains)
+}
+
+fn file_name(path: &str) -> &str {
+    path.rsplit('/').next().unwrap_or(path)
+}
+
+/// Little endian pointer of any width
+pub fn pointer_from(bytes: &[u8]) -> usize {
+    let mut buf = [0u8; 8];
+    buf[..bytes.len()].copy_from_slice(bytes);
+    u64::from_le_bytes(buf) as usize
+}
+
+/// An address pointing towards the target
+struct Node {
+    addr: usize,
+    /// Added to the pointer at `addr` to get to the next node (or the target)
+    offset: usize,
+    next: Option<usize>,
+}
+
+/// Finds chains of pointers from static module addresses to `target`.
+///
+/// Every aligned pointer sized value in the selected, readable, non executable maps that points into
+/// a readable map is collected and sorted. Starting from the target, pointers landing at most
+/// `max_offset` before it are followed backwards, level by level, up to `max_depth` levels.
+/// Addresses inside a module, or its `.bss`, end a chain.
+pub fn scan(
+    pid: &str,
+    maps: &[MemoryMap],
+    target: usize,
+    config: PointerScanConfig,
+    threads: usize,
+    control: &ScanControl,
+) -> io::Result<Vec<PointerChain>> {
+    let pointers = collect_pointers(pid, maps, config.width, threads, control)?;
+
+    let mut nodes: Vec<Node> = Vec::new();
+    let mut chains = Vec::new();
+    let mut seen = HashSet::from([target]);
+    let mut level: Vec<(usize, Option<usize>)> = vec![(target, None)];
+
+    for _ in 0..config.max_depth {
+        control.check()?;
+        let mut next_level = Vec::new();
+
+        for (addr, node) in level {
+            let lowest = addr.saturating_sub(config.max_offset);
+            let first = pointers.partition_point(|(value, _)| *value < lowest);
+
+            for (value, from) in pointers[first..].iter().take_while(|(v, _)| *v <= addr) {
+                // Addresses are only followed further once, but every way
+                // a static address leads to the target is a chain of its own
+                let first_visit = seen.insert(*from);
+                let module = module_at(maps, *from);
+                if !first_visit && module.is_none() {
+                    continue;
+                }
+
+                nodes.push(Node {
+                    addr: *from,
+                    offset: addr - value,
+                    next: node,
+                });
+                let idx = nodes.len() - 1;
+
+                if let Some((module, base)) = module {
+                    chains.push(build_chain(&nodes, idx, module, base));
+                    if chains.len() >= MAX_CHAINS {
+                        return Ok(chains);
+                    }
+                }
+
+                if first_visit && next_level.len() < MAX_LEVEL_NODES {
+                    next_level.push((*from, Some(idx)));
+                }
+            }
+        }
+
+        level = next_level;
+    }
+
+    Ok(chains)
+}
+
+/// `(value, address)` of every pointer into readable memory, sorted by value
+fn collect_pointers(
+    pid: &str,
+    maps: &[MemoryMap],
+    width: usize,
+    threads: usize,
+    control: &ScanControl,
+) -> io::Result<Vec<(usize, usize)>> {
+    let readable: Vec<(usize, usize)> = maps
+        .iter()
+        .filter(|m| m.perms.starts_with('r'))
+        .map(|m| (m.start, m.end))
+        .collect();
+    let is_valid = |value: usize| {
+        let idx = readable.partition_point(|(start, _)| *start <= value);
+        idx > 0 && value < readable[idx - 1].1
+    };
+
+    let sources: Vec<&MemoryMap> = pointer_sources(maps).collect();
+
+    let results = run_jobs(&sources, threads, |map| {
+        let mut found = Vec::new();
+
+        let res = for_each_chunk(pid, map, 0, |addr, chunk, owned| {
+            control.check()?;
+            let before = found.len();
+
+            for (i, bytes) in chunk[..owned].chunks_exact(width).enumerate() {
+                let value = pointer_from(bytes);
+                if is_valid(value) {
+                    found.push((value, addr + i * width));
+                }
+            }
+
+            control.scanned(owned, found.len() - before);
+            Ok(())
+        });
+        control.map_done();
+
+        res.map(|_| found)
+    });
+    control.check()?;
+
+    let mut pointers = Vec::new();
+    for res in results {
+        match res {
+            Ok(found) => pointers.extend(found),
+            Err(e) => {
+                trace_dbg!(e);
+            }
+        }
+    }
+
+    pointers.sort_unstable();
+    Ok(pointers)
+}
+
+/// The module an address is static in, and where that module is loaded.
+/// The anonymous map right after a module's last mapping is its `.bss`, so it counts too.
+fn module_at(maps: &[MemoryMap], addr: usize) -> Option<(&str, usize)> {
+    let idx = maps.partition_point(|m| m.start <= addr).checked_sub(1)?;
+    let map = maps.get(idx).filter(|m| addr < m.end)?;
+    if let Some(base) = map.base {
+        return Some((&map.name, base));
+    }
+
+    // `read_maps` names anonymous maps `-`
+    let prev = maps.get(idx.checked_sub(1)?)?;
+    match (map.name.as_str(), prev.base) {
+        ("-", Some(base)) if prev.end == map.start => Some((&prev.name, base)),
+        _ => None,
+    }
+}
+
+/// Maps pointers are looked for in, code doesn't hold any
+pub fn pointer_sources(maps: &[MemoryMap]) -> impl Iterator<Item = &MemoryMap> {
+    maps.iter()
+        .filter(|m| m.should_search && m.perms.starts_with('r') && !m.perms.contains('x'))
+}
+
+fn build_chain(nodes: &[Node], idx: usize, module: &str, base: usize) -> PointerChain {
+    let mut offsets = Vec::new();
+    let mut node = Some(idx);
+    while let Some(i) = node {
+        offsets.push(nodes[i].offset);
+        node = nodes[i].next;
+    }
+
+    PointerChain {
+        module: module.to_string(),
+        module_offset: nodes[idx].addr - base,
+        offsets,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chain(module: &str, offsets: &[usize]) -> PointerChain {
+        PointerChain {
+            module: module.to_string(),
+            module_offset: 0x1A2B30,
+            offsets: offsets.to_vec(),
+        }
+    }
+
+    #[test]
+    fn module_with_a_plus() {
+        let c = chain("libstdc++.so.6", &[0x18, 0x240]);
+        let text = c.to_string();
+        assert_eq!(text, "libstdc++.so.6+0x1A2B30 -> +0x18 -> +0x240");
+        assert_eq!(PointerChain::parse(&text), Ok(c));
+    }
+
+    #[test]
+    fn full_path_round_trip() {
+        let c = chain("/usr/lib/libstdc++.so.6", &[0x18]);
+        assert_eq!(c.to_string(), "libstdc++.so.6+0x1A2B30 -> +0x18");
+
+        let text = format!("{:#}", c);
+        assert_eq!(text, "/usr/lib/libstdc++.so.6+0x1A2B30 -> +0x18");
+        assert_eq!(PointerChain::parse(&text), Ok(c));
+    }
+
+    #[test]
+    fn negative_offsets_wrap() {
+        let c = chain("libgame.so", &[usize::MAX - 7, 0x10]);
+        let text = format!("{:#}", c);
+        assert_eq!(
+            text,
+            format!("libgame.so+0x1A2B30 -> +0x{:X} -> +0x10", usize::MAX - 7)
+        );
+        assert!(text.contains("FFF8 "));
+        assert_eq!(PointerChain::parse(&text), Ok(c));
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!(PointerChain::parse("libgame.so+0x10").is_err());
+        assert!(PointerChain::parse("+0x10 -> +0x18").is_err());
+        assert!(PointerChain::parse("libgame.so -> +0x18").is_err());
+        assert!(PointerChain::parse("libgame.so+0x10 -> 0x18").is_err());
+    }
+}
@@ -0,0 +1,256 @@
+use std::{
+    fs::File,
+    io,
+    os::unix::fs::FileExt,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use lazy_static::lazy_static;
+
+use crate::{logging::PROJECT_NAME, memory_model::PAGE_SIZE, trace_dbg};
+
+lazy_static! {
+    /// Set to `proc` to always go through `/proc/<pid>/mem`
+    pub static ref BACKEND_ENV: String = format!("{}_MEM_BACKEND", PROJECT_NAME.clone());
+    static ref FORCE_PROC_MEM: bool =
+        std::env::var(BACKEND_ENV.clone()).is_ok_and(|b| b == "proc");
+}
+
+/// Cleared once the kernel tells us it doesn't have `process_vm_readv`/`process_vm_writev`
+static VM_SYSCALLS: AtomicBool = AtomicBool::new(true);
+
+/// Set while nothing may be written to any process
+static READ_ONLY: AtomicBool = AtomicBool::new(false);
+
+/// Set by `--read-only`, read-only mode can't be left then
+static READ_ONLY_LOCKED: AtomicBool = AtomicBool::new(false);
+
+/// Most iovecs a single syscall accepts (`IOV_MAX`)
+pub const MAX_IOVECS: usize = 1024;
+
+fn use_vm_syscalls() -> bool {
+    !*FORCE_PROC_MEM && VM_SYSCALLS.load(Ordering::Relaxed)
+}
+
+/// Reads every `(start, end)` range, batching them into as few syscalls as possible.
+///
+/// Uses `process_vm_readv`, ranges it couldn't read are retried through `/proc/<pid>/mem`.
+/// The results are in the same order as the ranges.
+pub fn read_batch(pid: &str, ranges: &[(usize, usize)]) -> Vec<io::Result<Vec<u8>>> {
+    let mut bufs: Vec<Vec<u8>> = ranges
+        .iter()
+        .map(|(start, end)| vec![0u8; start.abs_diff(*end)])
+        .collect();
+    let mut done = vec![false; ranges.len()];
+
+    if let (true, Ok(pid)) = (use_vm_syscalls(), pid.parse::<libc::pid_t>()) {
+        let mut i = 0;
+        while i < ranges.len() {
+            let end = (i + MAX_IOVECS).min(ranges.len());
+
+            match vm_readv(pid, &ranges[i..end], &mut bufs[i..end]) {
+                Ok(read) => {
+                    // Reads stop at the first range that isn't fully readable
+                    let mut left = read;
+                    let mut complete = 0;
+                    for buf in &bufs[i..end] {
+                        if buf.len() > left {
+                            break;
+                        }
+                        left -= buf.len();
+                        complete += 1;
+                    }
+
+                    done[i..i + complete].fill(true);
+                    i += complete;
+                    // Leave the one that stopped the read for the fallback
+                    if i < end {
+                        i += 1;
+                    }
+                }
+                Err(e) if e.raw_os_error() == Some(libc::EFAULT) => i += 1,
+                Err(e) => {
+                    if e.raw_os_error() == Some(libc::ENOSYS) {
+                        VM_SYSCALLS.store(false, Ordering::Relaxed);
+                    }
+                    trace_dbg!(e);
+                    break;
+                }
+            }
+        }
+    }
+
+    let mut mem: Option<io::Result<File>> = None;
+    bufs.into_iter()
+        .zip(done)
+        .zip(ranges)
+        .map(|((mut buf, done), (start, _))| {
+            if done {
+                return Ok(buf);
+            }
+
+            let mem = mem.get_or_insert_with(|| File::open(mem_path(pid)));
+            match mem {
+                Ok(mem) => mem.read_exact_at(&mut buf, *start as u64).map(|_| buf),
+                Err(e) => Err(io::Error::new(e.kind(), e.to_string())),
+            }
+        })
+        .collect()
+}
+
+/// Reads `len` bytes from `start` on, `None` where they couldn't be read.
+/// Split on pages, so one unreadable page doesn't hide its neighbours.
+/// Stops at the end of the address space.
+pub fn read_pages(pid: &str, start: usize, len: usize) -> Vec<Option<u8>> {
+    let end = start.saturating_add(len);
+
+    let mut ranges = Vec::new();
+    let mut page = start;
+    while page < end {
+        let page_end = (page / PAGE_SIZE + 1)
+            .checked_mul(PAGE_SIZE)
+            .map_or(end, |e| e.min(end));
+        ranges.push((page, page_end));
+        page = page_end;
+    }
+
+    let mut bytes = Vec::with_capacity(end - start);
+    for ((start, end), read) in ranges.iter().zip(read_batch(pid, &ranges)) {
+        match read {
+            Ok(read) => bytes.extend(read.into_iter().map(Some)),
+            Err(_) => bytes.extend(std::iter::repeat_n(None, end - start)),
+        }
+    }
+    bytes
+}
+
+pub fn is_read_only() -> bool {
+    READ_ONLY.load(Ordering::Relaxed)
+}
+
+/// Whether read-only mode was forced by `--read-only`
+pub fn is_read_only_locked() -> bool {
+    READ_ONLY_LOCKED.load(Ordering::Relaxed)
+}
+
+/// Turns read-only mode on for good, for `--read-only`
+pub fn lock_read_only() {
+    READ_ONLY.store(true, Ordering::Relaxed);
+    READ_ONLY_LOCKED.store(true, Ordering::Relaxed);
+}
+
+/// Turns read-only mode on or off, returning whether it's on now
+pub fn toggle_read_only() -> Result<bool, String> {
+    if is_read_only_locked() {
+        return Err("Started with --read-only, writes can't be turned on".to_string());
+    }
+    Ok(!READ_ONLY.fetch_xor(true, Ordering::Relaxed))
+}
+
+/// Fails with a message for the footer in read-only mode
+pub fn check_writable() -> Result<(), String> {
+    match (is_read_only(), is_read_only_locked()) {
+        (true, true) => {
+            Err("Read-only mode, nothing gets written (started with --read-only)".to_string())
+        }
+        (true, false) => {
+            Err("Read-only mode, nothing gets written (F2 to allow writes)".to_string())
+        }
+        (false, _) => Ok(()),
+    }
+}
+
+/// Writes `bytes` at `addr`.
+///
+/// Uses `process_vm_writev`, falling back to `/proc/<pid>/mem` when that fails.
+/// The fallback can also write to read only pages, like code.
+/// Every write goes through here, so this is where read-only mode is enforced.
+pub fn write_mem(pid: &str, addr: usize, bytes: &[u8]) -> io::Result<()> {
+    if is_read_only() {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            "read-only mode",
+        ));
+    }
+
+    if let (true, Ok(pid)) = (use_vm_syscalls(), pid.parse::<libc::pid_t>()) {
+        match vm_writev(pid, addr, bytes) {
+            Ok(written) if written == bytes.len() => return Ok(()),
+            Ok(written) => {
+                trace_dbg!(written);
+            }
+            Err(e) => {
+                if e.raw_os_error() == Some(libc::ENOSYS) {
+                    VM_SYSCALLS.store(false, Ordering::Relaxed);
+                }
+                trace_dbg!(e);
+            }
+        }
+    }
+
+    let mem = File::options().write(true).open(mem_path(pid))?;
+    mem.write_all_at(bytes, addr as u64)
+}
+
+fn mem_path(pid: &str) -> String {
+    "/proc/".to_string() + pid + "/mem"
+}
+
+fn vm_readv(
+    pid: libc::pid_t,
+    ranges: &[(usize, usize)],
+    bufs: &mut [Vec<u8>],
+) -> io::Result<usize> {
+    let remote: Vec<libc::iovec> = ranges
+        .iter()
+        .zip(bufs.iter())
+        .map(|((start, _), buf)| libc::iovec {
+            iov_base: *start as *mut libc::c_void,
+            iov_len: buf.len(),
+        })
+        .collect();
+    let local: Vec<libc::iovec> = bufs
+        .iter_mut()
+        .map(|buf| libc::iovec {
+            iov_base: buf.as_mut_ptr().cast(),
+            iov_len: buf.len(),
+        })
+        .collect();
+
+    // SAFETY: The local iovecs cover buffers we own for their whole length,
+    // the remote ones are only dereferenced by the kernel in the other process
+    let read = unsafe {
+        libc::process_vm_readv(
+            pid,
+            local.as_ptr(),
+            local.len() as libc::c_ulong,
+            remote.as_ptr(),
+            remote.len() as libc::c_ulong,
+            0,
+        )
+    };
+
+    match read {
+        -1 => Err(io::Error::last_os_error()),
+        n => Ok(n as usize),
+    }
+}
+
+fn vm_writev(pid: libc::pid_t, addr: usize, bytes: &[u8]) -> io::Result<usize> {
+    let local = libc::iovec {
+        iov_base: bytes.as_ptr() as *mut libc::c_void,
+        iov_len: bytes.len(),
+    };
+    let remote = libc::iovec {
+        iov_base: addr as *mut libc::c_void,
+        iov_len: bytes.len(),
+    };
+
+    // SAFETY: The kernel only reads from the local iovec, which covers `bytes`
+    let written = unsafe { libc::process_vm_writev(pid, &local, 1, &remote, 1, 0) };
+
+    match written {
+        -1 => Err(io::Error::last_os_error()),
+        n => Ok(n as usize),
+    }
+}
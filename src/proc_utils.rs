@@ -1,4 +1,8 @@
-use std::{fs, io, usize};
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, Read},
+};
 
 use crate::{kunai::Task, memory_model::MemoryMap, utils::is_numeric};
 
@@ -9,7 +13,7 @@ pub fn get_tasks() -> Result<Vec<Task>, io::Error> {
     for pid in pids {
         match get_task_info(&pid) {
             Ok(info) => task_list.push(info),
-            Err(e) => {
+            Err(_e) => {
                 // println!("{:?}", e);
             }
         };
@@ -18,7 +22,7 @@ pub fn get_tasks() -> Result<Vec<Task>, io::Error> {
     Ok(task_list)
 }
 
-pub fn read_maps(pid: &String) -> Result<Vec<MemoryMap>, io::Error> {
+pub fn read_maps(pid: &str) -> Result<Vec<MemoryMap>, io::Error> {
     let mut maps = Vec::new();
 
     let maps_file = "/proc/".to_string() + pid + "/maps";
@@ -67,18 +71,35 @@ pub fn read_maps(pid: &String) -> Result<Vec<MemoryMap>, io::Error> {
         sline.next();
         sline.next();
 
-        let mem_name = match sline.next() {
-            Some(s) => s,
-            None => "-", // This can be None
-        };
+        let mem_name = sline.next().unwrap_or("-"); // This can be None
         mm.name = mem_name.to_string();
 
         maps.push(mm);
     }
 
+    // Maps come sorted by address, so the first one of a file is its base
+    let mut bases: HashMap<String, usize> = HashMap::new();
+    for map in maps.iter_mut().filter(|m| m.name.starts_with('/')) {
+        map.base = Some(*bases.entry(map.name.clone()).or_insert(map.start));
+    }
+
     Ok(maps)
 }
 
+/// Size of a pointer in the target, from the ELF class of its executable
+pub fn pointer_width(pid: &str) -> usize {
+    let mut ident = [0u8; 5];
+    let class = fs::File::open("/proc/".to_string() + pid + "/exe")
+        .and_then(|mut exe| exe.read_exact(&mut ident))
+        .map(|_| ident[4]);
+
+    match class {
+        Ok(1) => 4,
+        Ok(2) => 8,
+        _ => std::mem::size_of::<usize>(),
+    }
+}
+
 fn get_pids() -> Result<Vec<String>, io::Error> {
     let mut pids = Vec::new();
 
@@ -104,16 +125,13 @@ fn get_pids() -> Result<Vec<String>, io::Error> {
     Ok(pids)
 }
 
-pub fn get_task_info(pid: &String) -> Result<Task, io::Error> {
+pub fn get_task_info(pid: &str) -> Result<Task, io::Error> {
     let mut task = Task::new();
 
     task.pid = String::from(pid);
     task.cmdline = get_cmdline(pid);
 
-    let (name, state) = match get_pid_status(pid) {
-        Ok((n, s)) => (n, s),
-        Err(e) => return Err(e),
-    };
+    let (name, state) = get_pid_status(pid)?;
 
     task.name = name;
     task.state = state;
@@ -121,15 +139,12 @@ pub fn get_task_info(pid: &String) -> Result<Task, io::Error> {
     Ok(task)
 }
 
-fn get_pid_status(pid: &String) -> Result<(String, String), io::Error> {
+fn get_pid_status(pid: &str) -> Result<(String, String), io::Error> {
     let status_file = "/proc/".to_string() + pid + "/status";
     let mut state = String::new();
     let mut name = String::new();
 
-    let status = match fs::read_to_string(status_file) {
-        Ok(s) => s,
-        Err(e) => return Err(e),
-    };
+    let status = fs::read_to_string(status_file)?;
 
     for line in status.lines() {
         if line.contains("Name:") {
@@ -153,7 +168,7 @@ fn get_pid_status(pid: &String) -> Result<(String, String), io::Error> {
     Ok((name, state))
 }
 
-fn get_cmdline(pid: &String) -> String {
+fn get_cmdline(pid: &str) -> String {
     let cmdline_file = "/proc/".to_string() + pid + "/cmdline";
 
     match fs::read_to_string(cmdline_file) {
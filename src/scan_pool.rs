@@ -0,0 +1,59 @@
+use std::{
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+};
+
+use lazy_static::lazy_static;
+
+use crate::logging::PROJECT_NAME;
+
+lazy_static! {
+    pub static ref THREADS_ENV: String = format!("{}_THREADS", PROJECT_NAME.clone());
+}
+
+/// Number of scan workers, `KUNAI_THREADS` or one per core
+pub fn default_threads() -> usize {
+    match std::env::var(THREADS_ENV.clone()).map(|t| t.parse::<usize>()) {
+        Ok(Ok(n)) if n > 0 => n,
+        _ => thread::available_parallelism().map_or(1, |n| n.get()),
+    }
+}
+
+/// Runs `job` on every item using `threads` workers.
+/// Workers pick up the next item as soon as they are done, so uneven jobs balance out.
+/// The results are returned in the same order as the items.
+pub fn run_jobs<T, R, F>(items: &[T], threads: usize, job: F) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> R + Sync,
+{
+    let next = AtomicUsize::new(0);
+    let threads = threads.clamp(1, items.len().max(1));
+
+    let mut results: Vec<(usize, R)> = thread::scope(|s| {
+        let workers: Vec<_> = (0..threads)
+            .map(|_| {
+                s.spawn(|| {
+                    let mut done = Vec::new();
+                    loop {
+                        let idx = next.fetch_add(1, Ordering::Relaxed);
+                        match items.get(idx) {
+                            Some(item) => done.push((idx, job(item))),
+                            None => break,
+                        }
+                    }
+                    done
+                })
+            })
+            .collect();
+
+        workers
+            .into_iter()
+            .flat_map(|w| w.join().unwrap_or_default())
+            .collect()
+    });
+
+    results.sort_by_key(|(idx, _)| *idx);
+    results.into_iter().map(|(_, r)| r).collect()
+}
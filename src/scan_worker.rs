@@ -0,0 +1,430 @@
+use std::{
+    io,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{channel, Receiver, Sender, TryRecvError},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    address::format_hex,
+    memory_model::{search_maps, MemoryMap, SearchLocation, SearchQuery, JOB_SIZE},
+    next_scan::{CompareAgainst, NextScan},
+    pointer_scan::{self, pointer_sources, PointerChain, PointerScanConfig},
+    proc_mem::{read_batch, MAX_IOVECS},
+    snapshot::{Snapshot, MATERIALIZE_LIMIT},
+    trace_dbg,
+    value_type::{Endianness, ValueType},
+};
+
+/// Sent from the scan thread to the UI
+#[derive(Debug)]
+pub enum ScanMessage {
+    /// `bytes` more were scanned and `hits` more matches were found
+    Progress {
+        bytes: usize,
+        hits: usize,
+    },
+    MapDone,
+    Done {
+        outcome: ScanOutcome,
+        msg: String,
+    },
+}
+
+/// What the UI gets back once a scan is over.
+/// Cancelled and failed scans hand back what they were given.
+#[derive(Debug)]
+pub enum ScanOutcome {
+    Locations(Vec<SearchLocation>),
+    Snapshot(Snapshot),
+    Pointers(Vec<PointerChain>),
+    /// The scan thread went away without an answer, what it was given is lost
+    Failed,
+}
+
+/// The scans that can run in the background
+#[derive(Debug)]
+pub enum ScanRequest {
+    First {
+        query: SearchQuery,
+        maps: Vec<MemoryMap>,
+    },
+    Next {
+        scan: NextScan,
+        locations: Vec<SearchLocation>,
+    },
+    Unknown {
+        maps: Vec<MemoryMap>,
+        value_type: ValueType,
+        endianness: Endianness,
+        alignment: usize,
+    },
+    NextSnapshot {
+        scan: NextScan,
+        snapshot: Snapshot,
+    },
+    Pointers {
+        target: usize,
+        maps: Vec<MemoryMap>,
+        config: PointerScanConfig,
+    },
+}
+
+/// Handed to everything doing the actual scanning, to report progress and check for cancellation
+#[derive(Debug, Clone)]
+pub struct ScanControl {
+    tx: Sender<ScanMessage>,
+    cancelled: Arc<AtomicBool>,
+}
+
+/// Accumulated progress, as seen by the UI
+#[derive(Debug)]
+pub struct ScanProgress {
+    pub maps_done: usize,
+    pub maps_total: usize,
+    pub bytes_scanned: usize,
+    pub bytes_total: usize,
+    pub hits: usize,
+    pub started: Instant,
+}
+
+/// A scan running on a background thread
+#[derive(Debug)]
+pub struct ScanJob {
+    pub progress: ScanProgress,
+    rx: Receiver<ScanMessage>,
+    control: ScanControl,
+}
+
+impl ScanControl {
+    pub fn new() -> (ScanControl, Receiver<ScanMessage>) {
+        let (tx, rx) = channel();
+        let control = ScanControl {
+            tx,
+            cancelled: Arc::new(AtomicBool::new(false)),
+        };
+        (control, rx)
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Errors out once the scan is cancelled, so it can be used with `?` in chunk callbacks
+    pub fn check(&self) -> io::Result<()> {
+        match self.is_cancelled() {
+            true => Err(io::Error::new(io::ErrorKind::Interrupted, "Scan cancelled")),
+            false => Ok(()),
+        }
+    }
+
+    pub fn scanned(&self, bytes: usize, hits: usize) {
+        // The UI might be gone already, nothing to do about it
+        let _ = self.tx.send(ScanMessage::Progress { bytes, hits });
+    }
+
+    pub fn map_done(&self) {
+        let _ = self.tx.send(ScanMessage::MapDone);
+    }
+
+    fn done(&self, outcome: ScanOutcome, msg: String) {
+        let _ = self.tx.send(ScanMessage::Done { outcome, msg });
+    }
+}
+
+impl ScanProgress {
+    /// Estimated time left, based on how fast bytes were scanned so far
+    pub fn eta(&self) -> Option<Duration> {
+        if self.bytes_scanned == 0 {
+            return None;
+        }
+
+        let left = self.bytes_total.saturating_sub(self.bytes_scanned) as f64;
+        let rate = self.bytes_scanned as f64 / self.started.elapsed().as_secs_f64();
+        Some(Duration::from_secs_f64(left / rate))
+    }
+
+    /// Done fraction for the progress gauge
+    pub fn ratio(&self) -> f64 {
+        match self.bytes_total {
+            0 => 0.0,
+            total => (self.bytes_scanned as f64 / total as f64).min(1.0),
+        }
+    }
+}
+
+impl ScanJob {
+    /// Runs the request on a new thread
+    pub fn spawn(pid: &str, request: ScanRequest, threads: usize) -> ScanJob {
+        let (maps_total, bytes_total) = request.totals();
+        let (control, rx) = ScanControl::new();
+
+        let pid = pid.to_string();
+        let worker_control = control.clone();
+        thread::spawn(move || {
+            let (outcome, msg) = request.run(&pid, &worker_control, threads);
+            worker_control.done(outcome, msg);
+        });
+
+        ScanJob {
+            progress: ScanProgress {
+                maps_done: 0,
+                maps_total,
+                bytes_scanned: 0,
+                bytes_total,
+                hits: 0,
+                started: Instant::now(),
+            },
+            rx,
+            control,
+        }
+    }
+
+    pub fn cancel(&self) {
+        self.control.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.control.is_cancelled()
+    }
+
+    /// Applies the progress messages received so far, returning the outcome once done
+    pub fn poll(&mut self) -> Option<(ScanOutcome, String)> {
+        loop {
+            match self.rx.try_recv() {
+                Ok(ScanMessage::Progress { bytes, hits }) => {
+                    self.progress.bytes_scanned += bytes;
+                    self.progress.hits += hits;
+                }
+                Ok(ScanMessage::MapDone) => self.progress.maps_done += 1,
+                Ok(ScanMessage::Done { outcome, msg }) => return Some((outcome, msg)),
+                Err(TryRecvError::Empty) => return None,
+                Err(TryRecvError::Disconnected) => {
+                    let msg = "The scan stopped without a result, try scanning again".to_string();
+                    return Some((ScanOutcome::Failed, msg));
+                }
+            }
+        }
+    }
+}
+
+impl ScanRequest {
+    /// Number of maps and bytes the scan is going to go through
+    fn totals(&self) -> (usize, usize) {
+        match self {
+            ScanRequest::First { query, maps } => {
+                let maps: Vec<&MemoryMap> = maps.iter().filter(|m| query.should_scan(m)).collect();
+                // Maps are split into jobs, each reports being done
+                let jobs = maps
+                    .iter()
+                    .map(|m| (m.end - m.start).div_ceil(JOB_SIZE))
+                    .sum();
+                (jobs, maps.iter().map(|m| m.end - m.start).sum())
+            }
+            ScanRequest::Next { locations, .. } => {
+                let mut starts: Vec<usize> = locations.iter().map(|l| l.mem_info.start).collect();
+                starts.dedup();
+                (
+                    starts.len(),
+                    locations.iter().map(|l| l.end - l.start).sum(),
+                )
+            }
+            ScanRequest::Unknown { maps, .. } => {
+                let maps: Vec<&MemoryMap> = maps
+                    .iter()
+                    .filter(|m| m.should_search && m.perms.starts_with('r'))
+                    .collect();
+                (maps.len(), maps.iter().map(|m| m.end - m.start).sum())
+            }
+            ScanRequest::NextSnapshot { snapshot, .. } => {
+                let regions: Vec<&MemoryMap> = snapshot
+                    .regions
+                    .iter()
+                    .filter(|r| r.candidates > 0)
+                    .map(|r| &r.map)
+                    .collect();
+                (regions.len(), regions.iter().map(|m| m.end - m.start).sum())
+            }
+            ScanRequest::Pointers { maps, .. } => {
+                let maps: Vec<&MemoryMap> = pointer_sources(maps).collect();
+                (maps.len(), maps.iter().map(|m| m.end - m.start).sum())
+            }
+        }
+    }
+
+    fn run(self, pid: &str, control: &ScanControl, threads: usize) -> (ScanOutcome, String) {
+        match self {
+            ScanRequest::First { query, maps } => {
+                let locations = search_maps(pid, &query, &maps, threads, control);
+                if control.is_cancelled() {
+                    return (
+                        ScanOutcome::Locations(Vec::new()),
+                        "Scan cancelled".to_string(),
+                    );
+                }
+
+                let msg = format!("Found {} occurances!", locations.len());
+                (ScanOutcome::Locations(locations), msg)
+            }
+            ScanRequest::Next { scan, locations } => next_scan(pid, scan, locations, control),
+            ScanRequest::Unknown {
+                maps,
+                value_type,
+                endianness,
+                alignment,
+            } => match Snapshot::take(
+                pid, &maps, value_type, endianness, alignment, threads, control,
+            ) {
+                Ok(snapshot) => {
+                    let msg = format!(
+                        "Snapshot of {} regions taken, {} candidates",
+                        snapshot.regions.len(),
+                        snapshot.candidates()
+                    );
+                    (ScanOutcome::Snapshot(snapshot), msg)
+                }
+                Err(e) if control.is_cancelled() => {
+                    trace_dbg!(e);
+                    (
+                        ScanOutcome::Locations(Vec::new()),
+                        "Scan cancelled".to_string(),
+                    )
+                }
+                Err(e) => {
+                    let msg = format!("Failed to take snapshot: {}", e);
+                    trace_dbg!(e);
+                    (ScanOutcome::Locations(Vec::new()), msg)
+                }
+            },
+            ScanRequest::NextSnapshot { scan, snapshot } => {
+                next_snapshot_scan(pid, scan, snapshot, threads, control)
+            }
+            ScanRequest::Pointers {
+                target,
+                maps,
+                config,
+            } => match pointer_scan::scan(pid, &maps, target, config, threads, control) {
+                Ok(chains) => {
+                    let msg = format!(
+                        "Found {} pointer chains to {}",
+                        chains.len(),
+                        format_hex(target)
+                    );
+                    (ScanOutcome::Pointers(chains), msg)
+                }
+                Err(e) => {
+                    let msg = match control.is_cancelled() {
+                        true => "Scan cancelled".to_string(),
+                        false => format!("Pointer scan failed: {}", e),
+                    };
+                    trace_dbg!(e);
+                    (ScanOutcome::Pointers(Vec::new()), msg)
+                }
+            },
+        }
+    }
+}
+
+/// Re-reads every location and keeps only the ones matching the next scan comparison
+fn next_scan(
+    pid: &str,
+    scan: NextScan,
+    locations: Vec<SearchLocation>,
+    control: &ScanControl,
+) -> (ScanOutcome, String) {
+    let mut matching = Vec::new();
+    let mut current_map = locations.first().map(|l| l.mem_info.start);
+
+    // Read a batch of locations per syscall instead of one at a time
+    for batch in locations.chunks(MAX_IOVECS) {
+        if control.is_cancelled() {
+            return (
+                ScanOutcome::Locations(locations),
+                "Scan cancelled".to_string(),
+            );
+        }
+
+        let ranges: Vec<(usize, usize)> = batch.iter().map(|l| (l.start, l.end)).collect();
+        let values = read_batch(pid, &ranges);
+
+        for (location, value) in batch.iter().zip(values) {
+            if current_map != Some(location.mem_info.start) {
+                current_map = Some(location.mem_info.start);
+                control.map_done();
+            }
+
+            let current = match value {
+                Ok(v) => v,
+                Err(e) => {
+                    // Unreadable now, so it can't be what we're looking for
+                    trace_dbg!(e);
+                    continue;
+                }
+            };
+
+            let old = match scan.against {
+                CompareAgainst::Previous => &location.bytes,
+                CompareAgainst::Original => &location.first_bytes,
+            };
+
+            let matches = scan.matches(&current, old);
+            control.scanned(current.len(), matches as usize);
+
+            if matches {
+                let mut location = location.clone();
+                location.update(current);
+                matching.push(location);
+            }
+        }
+    }
+    control.map_done();
+
+    let msg = format!(
+        "Narrowed {} down to {} ({})",
+        locations.len(),
+        matching.len(),
+        scan.mode
+    );
+    (ScanOutcome::Locations(matching), msg)
+}
+
+/// Narrows down the snapshot, switching to a search list once it's small enough
+fn next_snapshot_scan(
+    pid: &str,
+    scan: NextScan,
+    mut snapshot: Snapshot,
+    threads: usize,
+    control: &ScanControl,
+) -> (ScanOutcome, String) {
+    let before = snapshot.candidates();
+    let after = match snapshot.next_scan(pid, &scan, threads, control) {
+        Ok(n) => n,
+        Err(e) => {
+            let msg = match control.is_cancelled() {
+                true => "Scan cancelled".to_string(),
+                false => format!("Snapshot scan failed: {}", e),
+            };
+            trace_dbg!(e);
+            return (ScanOutcome::Snapshot(snapshot), msg);
+        }
+    };
+
+    let msg = format!("Narrowed {} down to {} ({})", before, after, scan.mode);
+
+    if after > MATERIALIZE_LIMIT {
+        return (ScanOutcome::Snapshot(snapshot), msg);
+    }
+
+    match snapshot.locations() {
+        Ok(locations) => (ScanOutcome::Locations(locations), msg),
+        Err(e) => {
+            let msg = format!("Failed to read snapshot: {}", e);
+            trace_dbg!(e);
+            (ScanOutcome::Snapshot(snapshot), msg)
+        }
+    }
+}
@@ -0,0 +1,420 @@
+use std::{
+    fs::{self, File},
+    io,
+    ops::Range,
+    os::unix::fs::FileExt,
+    path::PathBuf,
+};
+
+use crate::{
+    logging::get_data_dir,
+    memory_model::{for_each_chunk, MemoryMap, SearchLocation},
+    next_scan::{CompareAgainst, NextScan},
+    scan_pool::run_jobs,
+    scan_worker::ScanControl,
+    trace_dbg,
+    value_type::{Endianness, ValueType},
+};
+
+/// Once this few candidates are left, they are turned into a regular search list
+pub const MATERIALIZE_LIMIT: usize = 100_000;
+
+/// State of an unknown initial value scan.
+///
+/// Every region is streamed to disk instead of being kept around as `SearchLocation`s.
+/// Each region has up to four files in `dir`:
+///  `<id>.orig` the bytes from the first scan
+///  `<id>.prev` the bytes from the last scan
+///  `<id>.last` the bytes from the scan before that, once there was a next scan
+///  `<id>.mask` one bit per aligned slot, set while the slot is still a candidate
+#[derive(Debug)]
+pub struct Snapshot {
+    pub dir: PathBuf,
+    pub regions: Vec<SnapshotRegion>,
+    pub value_type: ValueType,
+    pub endianness: Endianness,
+    pub alignment: usize,
+}
+
+#[derive(Debug)]
+pub struct SnapshotRegion {
+    pub id: usize,
+    pub map: MemoryMap,
+    pub candidates: usize,
+}
+
+impl Snapshot {
+    /// Copies every readable map into a fresh snapshot directory
+    pub fn take(
+        pid: &str,
+        maps: &[MemoryMap],
+        value_type: ValueType,
+        endianness: Endianness,
+        alignment: usize,
+        threads: usize,
+        control: &ScanControl,
+    ) -> io::Result<Snapshot> {
+        let size = match value_type.size() {
+            Some(s) => s,
+            None => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!(
+                        "Unknown value scans need a fixed size type, not {}",
+                        value_type
+                    ),
+                ))
+            }
+        };
+
+        let dir = get_data_dir().join("snapshots").join(pid);
+        if dir.exists() {
+            fs::remove_dir_all(&dir)?;
+        }
+        fs::create_dir_all(&dir)?;
+
+        let mut snapshot = Snapshot {
+            dir,
+            regions: Vec::new(),
+            value_type,
+            endianness,
+            alignment: alignment.max(1),
+        };
+
+        let wanted: Vec<(usize, &MemoryMap)> = maps
+            .iter()
+            .enumerate()
+            .filter(|(_, m)| m.should_search && m.perms.starts_with('r'))
+            .collect();
+
+        let regions = run_jobs(&wanted, threads, |(id, map)| {
+            let region = snapshot.take_region(pid, *id, map, size, control);
+            control.map_done();
+            region
+        });
+
+        // Dropping the half taken snapshot cleans up its directory
+        control.check()?;
+
+        for region in regions {
+            match region {
+                Ok(Some(r)) => snapshot.regions.push(r),
+                Ok(None) => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(snapshot)
+    }
+
+    /// Drops the candidates of regions whose map isn't wanted anymore
+    pub fn retain_regions<F: Fn(&MemoryMap) -> bool>(&mut self, keep: F) {
+        for region in &mut self.regions {
+            if !keep(&region.map) {
+                region.candidates = 0;
+            }
+        }
+    }
+
+    /// Total number of addresses still matching
+    pub fn candidates(&self) -> usize {
+        self.regions.iter().map(|r| r.candidates).sum()
+    }
+
+    /// Re-reads every region with candidates left and drops the slots not matching `scan`
+    ///
+    /// The new state is written next to the old one and only swapped in once every region is done,
+    /// so a cancelled or failed scan leaves the snapshot as it was.
+    pub fn next_scan(
+        &mut self,
+        pid: &str,
+        scan: &NextScan,
+        threads: usize,
+        control: &ScanControl,
+    ) -> io::Result<usize> {
+        let size = self.value_type.size().unwrap_or(1);
+
+        let results = run_jobs(&self.regions, threads, |region| {
+            if region.candidates == 0 {
+                return Ok(0);
+            }
+            let candidates = self.scan_region(pid, region, scan, size, control);
+            control.map_done();
+            candidates
+        });
+
+        let mut candidates = Vec::new();
+        for res in results {
+            match res {
+                Ok(n) => candidates.push(n),
+                Err(e) => {
+                    self.discard_next();
+                    return Err(e);
+                }
+            }
+        }
+        if let Err(e) = control.check() {
+            self.discard_next();
+            return Err(e);
+        }
+
+        for (region, candidates) in self.regions.iter_mut().zip(candidates) {
+            if region.candidates > 0 {
+                fs::rename(
+                    self.dir.join(format!("{}.prev", region.id)),
+                    self.dir.join(format!("{}.last", region.id)),
+                )?;
+                fs::rename(
+                    self.dir.join(format!("{}.prev.next", region.id)),
+                    self.dir.join(format!("{}.prev", region.id)),
+                )?;
+                fs::rename(
+                    self.dir.join(format!("{}.mask.next", region.id)),
+                    self.dir.join(format!("{}.mask", region.id)),
+                )?;
+            }
+            region.candidates = candidates;
+        }
+
+        Ok(self.candidates())
+    }
+
+    /// Removes the state written by an unfinished next scan
+    fn discard_next(&self) {
+        for region in &self.regions {
+            for kind in ["prev.next", "mask.next"] {
+                // Regions that weren't reached yet don't have these files
+                let _ = fs::remove_file(self.region_file(region, kind));
+            }
+        }
+    }
+
+    /// Turns the remaining candidates into search locations
+    pub fn locations(&self) -> io::Result<Vec<SearchLocation>> {
+        let size = self.value_type.size().unwrap_or(1);
+        let mut locations = Vec::new();
+
+        for region in &self.regions {
+            if region.candidates == 0 {
+                continue;
+            }
+
+            let prev = File::open(self.region_file(region, "prev"))?;
+            let orig = File::open(self.region_file(region, "orig"))?;
+            let mask = fs::read(self.region_file(region, "mask"))?;
+            // Right after the first scan, the previous value is the current one
+            let last = File::open(self.region_file(region, "last"))
+                .or_else(|_| File::open(self.region_file(region, "prev")))?;
+
+            for slot in 0..self.slot_count(&region.map, size) {
+                if !is_set(&mask, slot) {
+                    continue;
+                }
+
+                let offset = self.slot_offset(&region.map, slot);
+                let mut loc = SearchLocation::new();
+                loc.start = region.map.start + offset;
+                loc.end = loc.start + size;
+                loc.mem_info = region.map.clone();
+                loc.value_type = self.value_type;
+                loc.endianness = self.endianness;
+
+                loc.first_bytes = vec![0u8; size];
+                orig.read_exact_at(&mut loc.first_bytes, offset as u64)?;
+                loc.bytes = vec![0u8; size];
+                prev.read_exact_at(&mut loc.bytes, offset as u64)?;
+                let mut last_bytes = vec![0u8; size];
+                last.read_exact_at(&mut last_bytes, offset as u64)?;
+
+                loc.value = loc.decode(&loc.bytes);
+                loc.prev_value = loc.decode(&last_bytes);
+
+                locations.push(loc);
+            }
+        }
+
+        Ok(locations)
+    }
+
+    /// Streams a single map to disk, `None` if nothing in it is readable
+    fn take_region(
+        &self,
+        pid: &str,
+        id: usize,
+        map: &MemoryMap,
+        size: usize,
+        control: &ScanControl,
+    ) -> io::Result<Option<SnapshotRegion>> {
+        let slots = self.slot_count(map, size);
+        if slots == 0 {
+            return Ok(None);
+        }
+
+        let mut region = SnapshotRegion {
+            id,
+            map: map.clone(),
+            candidates: 0,
+        };
+
+        let orig = File::create(self.region_file(&region, "orig"))?;
+        orig.set_len((map.end - map.start) as u64)?;
+
+        // Slots in unreadable pages are never candidates
+        let mut mask = vec![0u8; slots.div_ceil(8)];
+
+        let res = for_each_chunk(pid, map, size - 1, |addr, chunk, owned| {
+            control.check()?;
+
+            let base = addr - map.start;
+            orig.write_all_at(&chunk[..owned], base as u64)?;
+
+            let found = region.candidates;
+            for slot in self.slot_range(map, base, base + owned, slots) {
+                let offset = self.slot_offset(map, slot);
+                if offset + size <= base + chunk.len() {
+                    set(&mut mask, slot);
+                    region.candidates += 1;
+                }
+            }
+
+            control.scanned(owned, region.candidates - found);
+            Ok(())
+        });
+
+        if let Err(e) = res {
+            trace_dbg!(e);
+            region.candidates = 0;
+        }
+
+        if region.candidates == 0 {
+            fs::remove_file(self.region_file(&region, "orig"))?;
+            return Ok(None);
+        }
+
+        fs::copy(
+            self.region_file(&region, "orig"),
+            self.region_file(&region, "prev"),
+        )?;
+        fs::write(self.region_file(&region, "mask"), &mask)?;
+
+        Ok(Some(region))
+    }
+
+    /// Compares a region against the scan, returning how many candidates are left in it
+    fn scan_region(
+        &self,
+        pid: &str,
+        region: &SnapshotRegion,
+        scan: &NextScan,
+        size: usize,
+        control: &ScanControl,
+    ) -> io::Result<usize> {
+        let map = &region.map;
+        let slots = self.slot_count(map, size);
+        let mask = fs::read(self.region_file(region, "mask"))?;
+        let prev = File::open(self.region_file(region, "prev"))?;
+        let orig = File::open(self.region_file(region, "orig"))?;
+
+        let next = File::create(self.region_file(region, "prev.next"))?;
+        next.set_len((map.end - map.start) as u64)?;
+        let compare_to = match scan.against {
+            CompareAgainst::Previous => &prev,
+            CompareAgainst::Original => &orig,
+        };
+
+        // Slots that aren't readable anymore are never visited and stay unset
+        let mut new_mask = vec![0u8; mask.len()];
+        let mut candidates = 0;
+        let mut old = Vec::new();
+
+        let res = for_each_chunk(pid, map, size - 1, |addr, chunk, owned| {
+            control.check()?;
+
+            let base = addr - map.start;
+            let found = candidates;
+            old.resize(chunk.len(), 0);
+            compare_to.read_exact_at(&mut old, base as u64)?;
+
+            for slot in self.slot_range(map, base, base + owned, slots) {
+                if !is_set(&mask, slot) {
+                    continue;
+                }
+
+                let offset = self.slot_offset(map, slot) - base;
+                if offset + size <= chunk.len()
+                    && scan.matches(&chunk[offset..offset + size], &old[offset..offset + size])
+                {
+                    set(&mut new_mask, slot);
+                    candidates += 1;
+                }
+            }
+
+            control.scanned(owned, candidates - found);
+            next.write_all_at(&chunk[..owned], base as u64)
+        });
+
+        match res {
+            Err(e) if control.is_cancelled() => return Err(e),
+            Err(e) => {
+                // The region is gone or unreadable, nothing in it can match
+                trace_dbg!(e);
+                candidates = 0;
+            }
+            Ok(_) => {}
+        }
+
+        fs::write(self.region_file(region, "mask.next"), &new_mask)?;
+
+        Ok(candidates)
+    }
+
+    fn region_file(&self, region: &SnapshotRegion, kind: &str) -> PathBuf {
+        self.dir.join(format!("{}.{}", region.id, kind))
+    }
+
+    /// Offset of the first aligned address in the map
+    fn first_offset(&self, map: &MemoryMap) -> usize {
+        (self.alignment - map.start % self.alignment) % self.alignment
+    }
+
+    /// Offset of a slot from the start of the map
+    fn slot_offset(&self, map: &MemoryMap, slot: usize) -> usize {
+        self.first_offset(map) + slot * self.alignment
+    }
+
+    /// Slots whose offset lies in `lo..hi`
+    fn slot_range(&self, map: &MemoryMap, lo: usize, hi: usize, slots: usize) -> Range<usize> {
+        let first = self.first_offset(map);
+        let to_slot = |offset: usize| offset.saturating_sub(first).div_ceil(self.alignment);
+
+        to_slot(lo).min(slots)..to_slot(hi).min(slots)
+    }
+
+    /// Number of aligned slots a value of `size` bytes fits in
+    fn slot_count(&self, map: &MemoryMap, size: usize) -> usize {
+        let len = map.end - map.start;
+        let first = self.first_offset(map);
+
+        if len < first + size {
+            0
+        } else {
+            (len - first - size) / self.alignment + 1
+        }
+    }
+}
+
+impl Drop for Snapshot {
+    fn drop(&mut self) {
+        if let Err(e) = fs::remove_dir_all(&self.dir) {
+            trace_dbg!(e);
+        }
+    }
+}
+
+fn is_set(mask: &[u8], bit: usize) -> bool {
+    mask[bit / 8] & (1 << (bit % 8)) != 0
+}
+
+fn set(mask: &mut [u8], bit: usize) {
+    mask[bit / 8] |= 1 << (bit % 8);
+}
@@ -0,0 +1,207 @@
+use crate::{
+    address::find_map,
+    memory_model::MemoryMap,
+    pointer_scan::pointer_from,
+    structs::{FieldDef, FieldKind, StructDef},
+    value_type::{Endianness, StrEncoding, ValueType},
+};
+
+/// Bytes looked at by an auto dissect
+pub const GUESS_SIZE: usize = 0x100;
+
+/// Size of the smallest field guessed, ints and floats
+const UNIT: usize = 4;
+
+/// Shortest run of printable characters taken as a string
+const MIN_STRING: usize = 4;
+
+/// Ints in this range are more likely counters, ids or flags than anything else
+const SMALL_INT: i64 = 1_000_000;
+
+/// Guesses a layout for `bytes`, read at the start of a struct.
+///
+/// Goes through the bytes 4 at a time, taking each spot as the first of: a pointer into
+/// one of `maps`, a plausible double, zero padding, an ASCII string, a plausible float,
+/// a small int. Pointers and doubles are only looked for at pointer aligned offsets.
+pub fn guess_layout(name: &str, bytes: &[u8], maps: &[MemoryMap], width: usize) -> StructDef {
+    let mut fields: Vec<FieldDef> = Vec::new();
+    let mut offset = 0;
+
+    while offset + UNIT <= bytes.len() {
+        let unit = &bytes[offset..offset + UNIT];
+        let slot = match offset % width == 0 {
+            true => bytes.get(offset..offset + width),
+            false => None,
+        };
+
+        // Whole slots first, a double's low half is often zero
+        if let Some(slot) = slot.filter(|s| s.iter().any(|b| *b != 0)) {
+            if find_map(maps, pointer_from(slot)).is_some() {
+                fields.push(field("ptr", offset, FieldKind::Pointer, None));
+                offset += width;
+                continue;
+            }
+            if width == 8 && is_plausible_double(slot) {
+                fields.push(field("f64", offset, FieldKind::Value(ValueType::F64), None));
+                offset += width;
+                continue;
+            }
+        }
+
+        if unit == [0, 0, 0, 0] {
+            // Grow the padding before this if there's some
+            match fields.last_mut() {
+                Some(f) if is_padding(f) && f.offset + f.length.unwrap_or(0) == offset => {
+                    f.length = Some(f.length.unwrap_or(0) + UNIT)
+                }
+                _ => fields.push(field(
+                    "pad",
+                    offset,
+                    FieldKind::Value(ValueType::Aob),
+                    Some(UNIT),
+                )),
+            }
+            offset += UNIT;
+            continue;
+        }
+
+        if let Some(length) = string_length(&bytes[offset..]) {
+            // Keep the fields after it aligned
+            let length = length.div_ceil(UNIT) * UNIT;
+            let kind = FieldKind::Value(ValueType::Str(StrEncoding::Utf8));
+            fields.push(field("str", offset, kind, Some(length)));
+            offset += length;
+            continue;
+        }
+
+        let value_type = match is_plausible_float(unit) {
+            true => ValueType::F32,
+            false if is_small_int(unit) => ValueType::I32,
+            false => ValueType::U32,
+        };
+        let name = value_type.to_string();
+        fields.push(field(&name, offset, FieldKind::Value(value_type), None));
+        offset += UNIT;
+    }
+
+    StructDef {
+        name: name.to_string(),
+        fields,
+        draft: true,
+    }
+}
+
+fn field(kind_name: &str, offset: usize, kind: FieldKind, length: Option<usize>) -> FieldDef {
+    FieldDef {
+        name: format!("{}_{:X}", kind_name, offset),
+        offset,
+        kind,
+        length,
+        endianness: Endianness::Little,
+    }
+}
+
+fn is_padding(field: &FieldDef) -> bool {
+    field.kind == FieldKind::Value(ValueType::Aob) && field.name.starts_with("pad_")
+}
+
+/// Bytes of the printable ASCII string at the start, including its NUL
+fn string_length(bytes: &[u8]) -> Option<usize> {
+    let printable = bytes
+        .iter()
+        .take_while(|b| b.is_ascii_graphic() || **b == b' ')
+        .count();
+
+    match bytes.get(printable) {
+        Some(0) | None if printable >= MIN_STRING => Some((printable + 1).min(bytes.len())),
+        _ => None,
+    }
+}
+
+fn is_plausible_float(bytes: &[u8]) -> bool {
+    let value = f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+    is_plausible(value.into()) && !is_small_int(bytes)
+}
+
+fn is_plausible_double(bytes: &[u8]) -> bool {
+    let mut buf = [0u8; 8];
+    buf.copy_from_slice(bytes);
+    // An int followed by a float can look like a double too
+    let low = &bytes[..4];
+    let halves_fit = low != [0, 0, 0, 0] && (is_small_int(low) || is_plausible_float(low));
+    is_plausible(f64::from_le_bytes(buf)) && !halves_fit
+}
+
+/// Something a program would store: not NaN, not denormal and not huge
+fn is_plausible(value: f64) -> bool {
+    value.is_finite() && (value == 0.0 || (1e-4..1e7).contains(&value.abs()))
+}
+
+fn is_small_int(bytes: &[u8]) -> bool {
+    let value = i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+    (-SMALL_INT..=SMALL_INT).contains(&value.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layout(bytes: &[u8], maps: &[MemoryMap]) -> Vec<(usize, String, Option<usize>)> {
+        guess_layout("test", bytes, maps, 8)
+            .fields
+            .into_iter()
+            .map(|f| (f.offset, f.kind.type_name(), f.length))
+            .collect()
+    }
+
+    #[test]
+    fn guesses_a_player() {
+        let mut heap = MemoryMap::new();
+        heap.start = 0x5000_0000;
+        heap.end = 0x5001_0000;
+
+        let mut bytes = Vec::new();
+        bytes.extend(b"HeroName\0\0\0\0\0\0\0\0");
+        bytes.extend(100i32.to_le_bytes());
+        bytes.extend(3.5f32.to_le_bytes());
+        bytes.extend(12.25f64.to_le_bytes());
+        bytes.extend(0x5000_0120u64.to_le_bytes());
+        bytes.extend(12i32.to_le_bytes());
+        bytes.extend(0.75f32.to_le_bytes());
+        bytes.extend(0xDEADBEEFu32.to_le_bytes());
+        bytes.extend([0; 24]);
+        bytes.extend(b"abcdefg\0");
+
+        let expected = [
+            (0x0, "utf8", Some(12)),
+            (0xC, "aob", Some(4)),
+            (0x10, "i32", None),
+            (0x14, "f32", None),
+            (0x18, "f64", None),
+            (0x20, "ptr", None),
+            (0x28, "i32", None),
+            (0x2C, "f32", None),
+            (0x30, "u32", None),
+            (0x34, "aob", Some(24)),
+            (0x4C, "utf8", Some(8)),
+        ];
+        let expected: Vec<_> = expected
+            .iter()
+            .map(|(o, t, l)| (*o, t.to_string(), *l))
+            .collect();
+        assert_eq!(layout(&bytes, &[heap]), expected);
+    }
+
+    #[test]
+    fn pointers_need_a_map() {
+        let bytes = 0x5000_0120u64.to_le_bytes();
+        assert_ne!(layout(&bytes, &[])[0].1, "ptr");
+    }
+
+    #[test]
+    fn short_tail_is_left_out() {
+        // Less than a unit left can't be a field
+        assert_eq!(layout(&[1, 0, 0, 0, 7, 7], &[]).len(), 1);
+        assert!(layout(&[], &[]).is_empty());
+    }
+}
@@ -0,0 +1,470 @@
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use ratatui::widgets::TableState;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    address::format_hex,
+    logging::get_data_dir,
+    pointer_scan::pointer_from,
+    proc_mem::read_pages,
+    value_type::{Endianness, ValueType},
+};
+
+/// Pointers are followed at most this deep, structs pointing at themselves would never end
+const MAX_DEPTH: usize = 8;
+
+/// Written when there's no definitions file yet, to show the syntax
+const EXAMPLE: &str = r#"# Struct definitions, offsets are in bytes from the start of the struct.
+# Types are i8..i64, u8..u64, f32, f64, utf8, utf16le, utf16be, latin1 and aob
+# (strings and aob need a length), `ptr` for a plain pointer,
+# `*Name` for a pointer to a struct and `Name` for a struct stored inline.
+#
+# [[structs]]
+# name = "Player"
+# fields = [
+#     { name = "name", offset = 0x0, type = "utf8", length = 16 },
+#     { name = "hp", offset = 0x10, type = "i32" },
+#     { name = "speed", offset = 0x14, type = "f32" },
+#     { name = "position", offset = 0x18, type = "Vec3" },
+#     { name = "weapon", offset = 0x28, type = "*Weapon" },
+# ]
+#
+# [[structs]]
+# name = "Vec3"
+# fields = [
+#     { name = "x", offset = 0x0, type = "f32" },
+#     { name = "y", offset = 0x4, type = "f32" },
+#     { name = "z", offset = 0x8, type = "f32" },
+# ]
+"#;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FieldKind {
+    Value(ValueType),
+    /// A pointer that isn't followed
+    Pointer,
+    /// A pointer to an instance of the named struct
+    StructPointer(String),
+    /// The named struct stored inline
+    Struct(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct FieldDef {
+    pub name: String,
+    pub offset: usize,
+    pub kind: FieldKind,
+    /// Bytes of strings and aob, numbers use the size of their type
+    pub length: Option<usize>,
+    pub endianness: Endianness,
+}
+
+#[derive(Debug, Clone)]
+pub struct StructDef {
+    pub name: String,
+    pub fields: Vec<FieldDef>,
+    /// Guessed by an auto dissect and not saved yet
+    pub draft: bool,
+}
+
+/// The definitions file, structs are stored as `[[structs]]`
+#[derive(Debug, Serialize, Deserialize)]
+struct StructsFile {
+    #[serde(default)]
+    structs: Vec<StructEntry>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct StructEntry {
+    name: String,
+    #[serde(default)]
+    fields: Vec<FieldEntry>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct FieldEntry {
+    name: String,
+    offset: usize,
+    #[serde(rename = "type")]
+    field_type: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    length: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    endianness: Option<String>,
+}
+
+impl FieldKind {
+    fn parse(name: &str) -> FieldKind {
+        if name == "ptr" {
+            return FieldKind::Pointer;
+        }
+        if let Some(target) = name.strip_prefix('*') {
+            return FieldKind::StructPointer(target.trim().to_string());
+        }
+        match ValueType::from_name(name) {
+            Ok(value_type) => FieldKind::Value(value_type),
+            Err(_) => FieldKind::Struct(name.to_string()),
+        }
+    }
+
+    /// The name used in the definitions file
+    pub fn type_name(&self) -> String {
+        match self {
+            FieldKind::Value(value_type) => value_type.to_string(),
+            FieldKind::Pointer => "ptr".to_string(),
+            FieldKind::StructPointer(name) => format!("*{}", name),
+            FieldKind::Struct(name) => name.to_string(),
+        }
+    }
+}
+
+impl StructDef {
+    /// Bytes up to the end of the last field
+    pub fn size(&self, defs: &[StructDef], width: usize) -> usize {
+        self.size_at_depth(defs, width, 0)
+    }
+
+    fn size_at_depth(&self, defs: &[StructDef], width: usize, depth: usize) -> usize {
+        self.fields
+            .iter()
+            .map(|f| f.offset.saturating_add(f.size_at_depth(defs, width, depth)))
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+impl FieldDef {
+    pub fn size(&self, defs: &[StructDef], width: usize) -> usize {
+        self.size_at_depth(defs, width, 0)
+    }
+
+    fn size_at_depth(&self, defs: &[StructDef], width: usize, depth: usize) -> usize {
+        match &self.kind {
+            FieldKind::Value(value_type) => value_type.size().or(self.length).unwrap_or(0),
+            FieldKind::Pointer | FieldKind::StructPointer(_) => width,
+            FieldKind::Struct(name) if depth < MAX_DEPTH => {
+                find_struct(defs, name).map_or(0, |d| d.size_at_depth(defs, width, depth + 1))
+            }
+            FieldKind::Struct(_) => 0,
+        }
+    }
+}
+
+pub fn find_struct<'a>(defs: &'a [StructDef], name: &str) -> Option<&'a StructDef> {
+    defs.iter().find(|d| d.name == name)
+}
+
+/// `name`, or `name_2`, `name_3`... if a saved struct is already called that
+pub fn unique_name(defs: &[StructDef], name: &str) -> String {
+    (1..)
+        .map(|n| match n {
+            1 => name.to_string(),
+            n => format!("{}_{}", name, n),
+        })
+        .find(|n| !defs.iter().any(|d| !d.draft && d.name == *n))
+        .unwrap_or_default()
+}
+
+/// Where struct definitions are kept, shared by every process
+pub fn structs_path() -> PathBuf {
+    get_data_dir().join("structs.toml")
+}
+
+/// Reads the struct definitions, writing an example file when there's none yet
+pub fn load_structs(path: &Path) -> Result<Vec<StructDef>, String> {
+    if !path.exists() {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        }
+        fs::write(path, EXAMPLE).map_err(|e| e.to_string())?;
+    }
+
+    let content = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let file: StructsFile = toml::from_str(&content).map_err(|e| e.to_string())?;
+
+    let mut defs = Vec::new();
+    for s in file.structs {
+        let mut fields = Vec::new();
+        for f in s.fields {
+            let kind = FieldKind::parse(&f.field_type);
+            if let FieldKind::Value(value_type) = kind {
+                if value_type.size().is_none() && f.length.is_none() {
+                    return Err(format!("{}.{} needs a length", s.name, f.name));
+                }
+            }
+
+            fields.push(FieldDef {
+                name: f.name,
+                offset: f.offset,
+                kind,
+                length: f.length,
+                endianness: match f.endianness {
+                    Some(e) => Endianness::from_name(&e)?,
+                    None => Endianness::Little,
+                },
+            });
+        }
+        fields.sort_by_key(|f| f.offset);
+        defs.push(StructDef {
+            name: s.name,
+            fields,
+            draft: false,
+        });
+    }
+
+    // Only the first of two structs with the same name could ever be used
+    for (i, def) in defs.iter().enumerate() {
+        if defs[..i].iter().any(|d| d.name == def.name) {
+            return Err(format!("{} is defined twice", def.name));
+        }
+    }
+
+    // Catch typos in type names now rather than showing empty structs
+    for def in &defs {
+        for field in &def.fields {
+            if let FieldKind::Struct(name) | FieldKind::StructPointer(name) = &field.kind {
+                if find_struct(&defs, name).is_none() {
+                    return Err(format!(
+                        "{}.{} has unknown type '{}'",
+                        def.name,
+                        field.name,
+                        field.kind.type_name()
+                    ));
+                }
+            }
+        }
+    }
+
+    Ok(defs)
+}
+
+/// Adds a definition to the end of the file, leaving what's there (and its comments) alone
+pub fn append_struct(path: &Path, def: &StructDef) -> Result<(), String> {
+    let file = StructsFile {
+        structs: vec![StructEntry {
+            name: def.name.clone(),
+            fields: def
+                .fields
+                .iter()
+                .map(|f| FieldEntry {
+                    name: f.name.clone(),
+                    offset: f.offset,
+                    field_type: f.kind.type_name(),
+                    length: f.length,
+                    endianness: match f.endianness {
+                        Endianness::Little => None,
+                        Endianness::Big => Some(f.endianness.to_string()),
+                    },
+                })
+                .collect(),
+        }],
+    };
+
+    let content = toml::to_string(&file).map_err(|e| e.to_string())?;
+    let mut old = fs::read_to_string(path).unwrap_or_default();
+    if !old.is_empty() && !old.ends_with('\n') {
+        old.push('\n');
+    }
+    fs::write(path, old + "\n" + &content).map_err(|e| e.to_string())
+}
+
+/// A line of the struct tree
+#[derive(Debug, Clone)]
+pub struct StructRow {
+    pub depth: usize,
+    pub name: String,
+    pub type_name: String,
+    pub offset: usize,
+    /// `None` when the offset runs past the end of the address space
+    pub addr: Option<usize>,
+    pub value: String,
+    /// Field indices from the root struct down to this field
+    pub path: Vec<usize>,
+    /// Pointers to structs and inline structs can be expanded
+    pub expandable: bool,
+    pub expanded: bool,
+    /// Set for plain values, which can be edited
+    pub value_type: Option<(ValueType, Endianness, usize)>,
+}
+
+/// A struct instance shown as a tree
+#[derive(Debug)]
+pub struct StructView {
+    pub addr: Option<usize>,
+    /// Index of the struct definition the memory is shown as
+    pub def: usize,
+    pub expanded: HashSet<Vec<usize>>,
+    pub rows: Vec<StructRow>,
+    pub table_state: TableState,
+}
+
+impl StructView {
+    pub fn new() -> StructView {
+        StructView {
+            addr: None,
+            def: 0,
+            expanded: HashSet::new(),
+            rows: Vec::new(),
+            table_state: TableState::new(),
+        }
+    }
+
+    pub fn open(&mut self, addr: usize) {
+        if self.addr != Some(addr) {
+            self.expanded.clear();
+            self.table_state.select(None);
+        }
+        self.addr = Some(addr);
+    }
+
+    pub fn selected_row(&self) -> Option<&StructRow> {
+        self.rows.get(self.table_state.selected()?)
+    }
+
+    pub fn toggle_selected(&mut self) {
+        let path = match self.selected_row() {
+            Some(row) if row.expandable => row.path.clone(),
+            _ => return,
+        };
+        if !self.expanded.remove(&path) {
+            self.expanded.insert(path);
+        }
+    }
+
+    /// Reads the instance again and rebuilds the tree
+    pub fn refresh(&mut self, pid: &str, defs: &[StructDef], width: usize) {
+        self.rows = Vec::new();
+        let (addr, def) = match (self.addr, defs.get(self.def)) {
+            (Some(a), Some(d)) => (a, d),
+            _ => return,
+        };
+
+        let bytes = read_struct(pid, addr, def.size(defs, width));
+        let mut tree = Tree {
+            defs,
+            width,
+            pid,
+            expanded: &self.expanded,
+            rows: &mut self.rows,
+        };
+        tree.add_fields(def, Some(addr), &bytes, &mut Vec::new());
+    }
+}
+
+/// Most bytes read for a struct instance, fields past it show as unreadable.
+/// Keeps a mistyped offset from reading megabytes on every refresh.
+const MAX_READ: usize = 64 * 1024;
+
+/// The bytes at `addr`, `None` where they couldn't be read
+fn read_struct(pid: &str, addr: usize, size: usize) -> Vec<Option<u8>> {
+    read_pages(pid, addr, size.min(MAX_READ))
+}
+
+/// Builds the rows of a struct instance and whatever is expanded under it
+struct Tree<'a> {
+    defs: &'a [StructDef],
+    width: usize,
+    pid: &'a str,
+    expanded: &'a HashSet<Vec<usize>>,
+    rows: &'a mut Vec<StructRow>,
+}
+
+impl Tree<'_> {
+    /// `bytes` is what's at `addr`, `None` where it couldn't be read
+    fn add_fields(
+        &mut self,
+        def: &StructDef,
+        addr: Option<usize>,
+        bytes: &[Option<u8>],
+        path: &mut Vec<usize>,
+    ) {
+        for (i, field) in def.fields.iter().enumerate() {
+            path.push(i);
+            self.add_field(field, addr, bytes, path);
+            path.pop();
+        }
+    }
+
+    fn add_field(
+        &mut self,
+        field: &FieldDef,
+        base: Option<usize>,
+        bytes: &[Option<u8>],
+        path: &mut Vec<usize>,
+    ) {
+        let size = field.size(self.defs, self.width);
+        // Garbage pointers like 0xfffffffffffffff8 put fields past the end of the address space
+        let addr = base.and_then(|b| b.checked_add(field.offset));
+        let field_slice = field
+            .offset
+            .checked_add(size)
+            .and_then(|end| bytes.get(field.offset..end))
+            .unwrap_or_default();
+        let field_bytes: Option<Vec<u8>> = match addr {
+            Some(_) if field_slice.len() == size => field_slice.iter().copied().collect(),
+            _ => None,
+        };
+        let expanded = self.expanded.contains(path);
+
+        let mut row = StructRow {
+            depth: path.len() - 1,
+            name: field.name.clone(),
+            type_name: field.kind.type_name(),
+            offset: field.offset,
+            addr,
+            value: String::new(),
+            path: path.clone(),
+            expandable: false,
+            expanded,
+            value_type: None,
+        };
+
+        let mut pointer = None;
+        match &field.kind {
+            FieldKind::Value(value_type) => {
+                row.value = match &field_bytes {
+                    Some(b) => value_type.decode(b, field.endianness),
+                    None => "??".to_string(),
+                };
+                row.value_type = Some((*value_type, field.endianness, size));
+            }
+            FieldKind::Pointer | FieldKind::StructPointer(_) => {
+                pointer = field_bytes.as_deref().map(pointer_from);
+                row.value = match pointer {
+                    Some(0) => "null".to_string(),
+                    Some(p) => format!("-> {}", format_hex(p)),
+                    None => "??".to_string(),
+                };
+                row.expandable = matches!(field.kind, FieldKind::StructPointer(_))
+                    && pointer.is_some_and(|p| p != 0);
+            }
+            FieldKind::Struct(_) => row.expandable = true,
+        }
+
+        let depth_left = path.len() < MAX_DEPTH;
+        self.rows.push(row);
+        if !expanded || !depth_left {
+            return;
+        }
+
+        match &field.kind {
+            FieldKind::Struct(name) => {
+                if let Some(def) = find_struct(self.defs, name) {
+                    self.add_fields(def, addr, field_slice, path);
+                }
+            }
+            FieldKind::StructPointer(name) => {
+                if let (Some(def), Some(target)) = (find_struct(self.defs, name), pointer) {
+                    let bytes = read_struct(self.pid, target, def.size(self.defs, self.width));
+                    self.add_fields(def, Some(target), &bytes, path);
+                }
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,196 @@
+use std::{
+    collections::HashMap,
+    fs,
+    sync::{Arc, Mutex},
+};
+
+use lazy_static::lazy_static;
+
+use crate::memory_model::MemoryMap;
+
+const SHT_SYMTAB: u32 = 2;
+const SHT_DYNSYM: u32 = 11;
+const PT_LOAD: u32 = 1;
+const STT_OBJECT: u8 = 1;
+const STT_FUNC: u8 = 2;
+const STT_GNU_IFUNC: u8 = 10;
+
+lazy_static! {
+    /// Symbols of every module looked at so far, by path
+    static ref CACHE: Mutex<HashMap<String, Arc<ModuleSymbols>>> = Mutex::new(HashMap::new());
+}
+
+/// Functions and variables of an ELF file, from `.symtab` and `.dynsym`
+#[derive(Debug, Default)]
+pub struct ModuleSymbols {
+    /// Virtual address the start of the file is loaded at, before relocation
+    load_vaddr: u64,
+    /// Name to virtual address
+    symbols: HashMap<String, u64>,
+}
+
+impl ModuleSymbols {
+    /// Address of the symbol in a module loaded at `base`
+    pub fn address(&self, name: &str, base: usize) -> Option<usize> {
+        let vaddr = self.symbols.get(name)?;
+        Some(base.wrapping_add(vaddr.wrapping_sub(self.load_vaddr) as usize))
+    }
+}
+
+/// Looks a symbol up in the loaded modules, the executable first.
+/// `module!symbol` only looks in that module.
+pub fn find_symbol(pid: &str, maps: &[MemoryMap], name: &str) -> Option<usize> {
+    let (module, name) = match name.split_once('!') {
+        Some((m, n)) => (Some(m), n),
+        None => (None, name),
+    };
+
+    let mut modules: Vec<(&str, usize)> = Vec::new();
+    for map in maps {
+        let base = match map.base {
+            Some(b) if !modules.iter().any(|(m, _)| *m == map.name) => b,
+            _ => continue,
+        };
+        let file_name = map.name.rsplit('/').next().unwrap_or(&map.name);
+        if module.is_none_or(|m| m == map.name || m == file_name) {
+            modules.push((&map.name, base));
+        }
+    }
+
+    modules
+        .into_iter()
+        .find_map(|(path, base)| module_symbols(pid, path)?.address(name, base))
+}
+
+/// Symbols of a module, read once and cached.
+/// Files that aren't ELF (or are gone) have no symbols.
+fn module_symbols(pid: &str, path: &str) -> Option<Arc<ModuleSymbols>> {
+    let mut cache = CACHE.lock().ok()?;
+    if let Some(symbols) = cache.get(path) {
+        return Some(symbols.clone());
+    }
+
+    // Through the process root, in case it lives in another mount namespace
+    let data = fs::read(format!("/proc/{}/root{}", pid, path))
+        .or_else(|_| fs::read(path))
+        .ok();
+    let symbols = Arc::new(data.and_then(|d| parse_elf(&d)).unwrap_or_default());
+    cache.insert(path.to_string(), symbols.clone());
+    Some(symbols)
+}
+
+/// Little endian ELF reader, 32 or 64 bit
+struct Elf<'a> {
+    data: &'a [u8],
+    is_64: bool,
+}
+
+impl Elf<'_> {
+    /// Offsets come straight from the file, so garbage ones give `None` instead of overflowing
+    fn uint(&self, base: usize, offset: usize, size: usize) -> Option<u64> {
+        let start = base.checked_add(offset)?;
+        let bytes = self.data.get(start..start.checked_add(size)?)?;
+        let mut buf = [0u8; 8];
+        buf[..size].copy_from_slice(bytes);
+        Some(u64::from_le_bytes(buf))
+    }
+
+    /// A field that's 4 bytes in 32 bit files and 8 in 64 bit ones
+    fn word(&self, base: usize, offset32: usize, offset64: usize) -> Option<u64> {
+        match self.is_64 {
+            true => self.uint(base, offset64, 8),
+            false => self.uint(base, offset32, 4),
+        }
+    }
+
+    fn c_str(&self, offset: usize) -> Option<&str> {
+        let bytes = self.data.get(offset..)?;
+        let end = memchr::memchr(0, bytes)?;
+        std::str::from_utf8(&bytes[..end]).ok()
+    }
+}
+
+fn parse_elf(data: &[u8]) -> Option<ModuleSymbols> {
+    // Only little endian files, like everything else here
+    if data.get(..4)? != b"\x7fELF" || *data.get(5)? != 1 {
+        return None;
+    }
+    let elf = Elf {
+        data,
+        is_64: *data.get(4)? == 2,
+    };
+
+    // Program headers, for where the file starts in memory
+    let phoff = elf.word(0, 0x1C, 0x20)? as usize;
+    let phentsize = elf.uint(0, if elf.is_64 { 0x36 } else { 0x2A }, 2)? as usize;
+    let phnum = elf.uint(0, if elf.is_64 { 0x38 } else { 0x2C }, 2)? as usize;
+
+    let mut load_vaddr = None;
+    for i in 0..phnum {
+        let ph = phoff.checked_add(i * phentsize)?;
+        if elf.uint(ph, 0, 4)? != PT_LOAD as u64 {
+            continue;
+        }
+        let offset = elf.word(ph, 0x4, 0x8)?;
+        let vaddr = elf.word(ph, 0x8, 0x10)?;
+        let align = elf.word(ph, 0x1C, 0x30)?.max(1);
+        // The first loaded segment is the one mapped at the lowest address
+        load_vaddr = Some(vaddr.checked_sub(offset)? & !(align - 1));
+        break;
+    }
+
+    let shoff = elf.word(0, 0x20, 0x28)? as usize;
+    let shentsize = elf.uint(0, if elf.is_64 { 0x3A } else { 0x2E }, 2)? as usize;
+    let shnum = elf.uint(0, if elf.is_64 { 0x3C } else { 0x30 }, 2)? as usize;
+    let section = |idx: usize| shoff.checked_add(idx.checked_mul(shentsize)?);
+
+    let mut symbols = HashMap::new();
+    for i in 0..shnum {
+        let sh = section(i)?;
+        let sh_type = elf.uint(sh, 0x4, 4)? as u32;
+        if sh_type != SHT_SYMTAB && sh_type != SHT_DYNSYM {
+            continue;
+        }
+
+        let offset = elf.word(sh, 0x10, 0x18)? as usize;
+        let size = elf.word(sh, 0x14, 0x20)? as usize;
+        let link = elf.uint(sh, if elf.is_64 { 0x28 } else { 0x18 }, 4)? as usize;
+        let entsize = elf.word(sh, 0x24, 0x38)? as usize;
+        let strtab = elf.word(section(link)?, 0x10, 0x18)? as usize;
+        if entsize == 0 {
+            continue;
+        }
+
+        for sym in (offset..offset.checked_add(size)?).step_by(entsize) {
+            let (info, shndx, value) = match elf.is_64 {
+                true => (
+                    elf.uint(sym, 0x4, 1)?,
+                    elf.uint(sym, 0x6, 2)?,
+                    elf.uint(sym, 0x8, 8)?,
+                ),
+                false => (
+                    elf.uint(sym, 0xC, 1)?,
+                    elf.uint(sym, 0xE, 2)?,
+                    elf.uint(sym, 0x4, 4)?,
+                ),
+            };
+
+            // Skip undefined symbols, those live in another module
+            let kind = (info & 0xF) as u8;
+            if shndx == 0 || value == 0 || ![STT_OBJECT, STT_FUNC, STT_GNU_IFUNC].contains(&kind) {
+                continue;
+            }
+
+            let name = match elf.c_str(strtab.checked_add(elf.uint(sym, 0, 4)? as usize)?) {
+                Some(n) if !n.is_empty() => n,
+                _ => continue,
+            };
+            symbols.entry(name.to_string()).or_insert(value);
+        }
+    }
+
+    Some(ModuleSymbols {
+        load_vaddr: load_vaddr?,
+        symbols,
+    })
+}
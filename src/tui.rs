@@ -1,4 +1,3 @@
-use core::panic;
 use std::io::{self, stdout, Stdout};
 
 use crossterm::{execute, terminal::*};
@@ -22,6 +22,11 @@ pub enum SubScreen {
     MemoryMaps,
     MemorySearch,
     ValueEditing,
+    PointerScan,
+    AddressList,
+    HexView,
+    StructView,
+    Journal,
 }
 
 pub fn render_ui(frame: &mut Frame, kunai: &mut Kunai) {
@@ -1,6 +1,3 @@
-use core::panic;
-use std::{borrow::Cow, usize};
-
 use ratatui::layout::{Constraint, Direction, Layout, Rect};
 
 pub fn is_numeric(n: &str) -> bool {
@@ -25,6 +22,74 @@ pub fn bytes_to_string(value: Vec<u8>) -> String {
     }
 }
 
+/// Matches `text` against a glob where `*` is any run of chars and `?` is any single char
+pub fn glob_match(glob: &str, text: &str) -> bool {
+    let glob: Vec<char> = glob.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+
+    // Where to resume after the last `*` when the chars stop matching
+    let mut star: Option<(usize, usize)> = None;
+    let (mut g, mut t) = (0, 0);
+
+    while t < text.len() {
+        match glob.get(g) {
+            Some('*') => {
+                star = Some((g, t));
+                g += 1;
+            }
+            Some(c) if *c == '?' || *c == text[t] => {
+                g += 1;
+                t += 1;
+            }
+            _ => match star {
+                Some((star_g, star_t)) => {
+                    // Let the `*` eat one more char
+                    star = Some((star_g, star_t + 1));
+                    g = star_g + 1;
+                    t = star_t + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+
+    glob[g..].iter().all(|c| *c == '*')
+}
+
+/// Formats a byte count with a binary unit, e.g. `1.5 GiB`
+pub fn human_bytes(n: usize) -> String {
+    let units = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut size = n as f64;
+    let mut unit = 0;
+
+    while size >= 1024.0 && unit < units.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    match unit {
+        0 => format!("{} {}", n, units[0]),
+        _ => format!("{:.1} {}", size, units[unit]),
+    }
+}
+
+/// Formats bytes as space separated hex, e.g. `48 8b 05`
+pub fn bytes_to_hex(value: &[u8]) -> String {
+    value
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+/// Parses space separated hex bytes, as written by `bytes_to_hex`
+pub fn hex_to_bytes(input: &str) -> Result<Vec<u8>, String> {
+    input
+        .split_whitespace()
+        .map(|b| u8::from_str_radix(b, 16).map_err(|_| format!("'{}' is not a hex byte", b)))
+        .collect()
+}
+
 fn to_ascii(i: u8) -> String {
     // Taken from https://www.cs.cmu.edu/~pattis/15-1XX/common/handouts/ascii.html
     match i {
@@ -34,7 +99,7 @@ fn to_ascii(i: u8) -> String {
             "SUB", "ESC", "FS", "GS", "RS", "US", "SPACE",
         ][x as usize]
             .into(),
-        x @ 33..=126 => format!("{}", x as u8 as char).into(),
+        x @ 33..=126 => format!("{}", x as char),
         127 => "DEL".into(),
         _ => "\0".into(),
     }
@@ -62,3 +127,35 @@ pub fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
         ])
         .split(popup_layout[1])[1] // Return the middle chunk
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glob_stars() {
+        assert!(glob_match("*.so", "libc.so"));
+        assert!(glob_match("lib*", "libc.so.6"));
+        assert!(glob_match("lib*.so*", "libstdc++.so.6"));
+        assert!(glob_match("*", ""));
+        assert!(glob_match("a**b", "ab"));
+        assert!(!glob_match("*.so", "libc.so.6"));
+        assert!(!glob_match("lib*", "glibc"));
+    }
+
+    #[test]
+    fn glob_question_mark() {
+        assert!(glob_match("libc.so.?", "libc.so.6"));
+        assert!(glob_match("[?eap]", "[heap]"));
+        assert!(!glob_match("libc.so.?", "libc.so.12"));
+        assert!(!glob_match("?", ""));
+    }
+
+    #[test]
+    fn glob_empty_and_exact() {
+        assert!(glob_match("", ""));
+        assert!(!glob_match("", "[heap]"));
+        assert!(glob_match("[heap]", "[heap]"));
+        assert!(!glob_match("[heap]", "[stack]"));
+    }
+}
@@ -0,0 +1,503 @@
+use std::fmt;
+
+use crate::{
+    aob::Pattern,
+    utils::{bytes_to_hex, bytes_to_string},
+};
+
+/// The type a search string is interpreted as when scanning memory
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueType {
+    Str(StrEncoding),
+    I8,
+    I16,
+    I32,
+    I64,
+    U8,
+    U16,
+    U32,
+    U64,
+    F32,
+    F64,
+    /// Array of bytes, searched as a pattern with wildcards
+    Aob,
+    /// Byte oriented regular expression, matches have variable lengths
+    Regex,
+}
+
+/// How strings are laid out in memory
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StrEncoding {
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    Latin1,
+}
+
+/// How a string shorter than the one it overwrites is written
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StrFill {
+    /// Only the typed characters, the rest of the old string stays
+    AsTyped,
+    /// The typed characters and a NUL if there's room for it
+    NulTerminated,
+    /// The typed characters and zeros up to the old length
+    ZeroPadded,
+}
+
+/// Byte order used to encode/decode numeric values
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Endianness {
+    Little,
+    Big,
+}
+
+/// A decoded numeric value, used to compare values across scans
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub enum Number {
+    Int(i128),
+    Float(f64),
+}
+
+impl Number {
+    /// `self - other`, `None` if the two aren't of the same kind
+    pub fn delta(self, other: Number) -> Option<Number> {
+        match (self, other) {
+            (Number::Int(a), Number::Int(b)) => Some(Number::Int(a - b)),
+            (Number::Float(a), Number::Float(b)) => Some(Number::Float(a - b)),
+            _ => None,
+        }
+    }
+
+    /// Equality with a bit of slack for floats, which rarely match exactly
+    pub fn approx_eq(self, other: Number) -> bool {
+        match (self, other) {
+            (Number::Int(a), Number::Int(b)) => a == b,
+            (Number::Float(a), Number::Float(b)) => (a - b).abs() <= 1e-6 * a.abs().max(1.0),
+            _ => false,
+        }
+    }
+}
+
+/// Converts a value into little or big endian bytes
+macro_rules! to_bytes {
+    ($v:expr, $endian:expr) => {
+        match $endian {
+            Endianness::Little => $v.to_le_bytes().to_vec(),
+            Endianness::Big => $v.to_be_bytes().to_vec(),
+        }
+    };
+}
+
+/// Reads a `$t` from the bytes, `None` if the length doesn't match
+macro_rules! from_bytes {
+    ($t:ty, $bytes:expr, $endian:expr) => {
+        match <[u8; std::mem::size_of::<$t>()]>::try_from($bytes) {
+            Ok(b) => Some(match $endian {
+                Endianness::Little => <$t>::from_le_bytes(b),
+                Endianness::Big => <$t>::from_be_bytes(b),
+            }),
+            Err(_) => None,
+        }
+    };
+}
+
+impl ValueType {
+    /// Cycles through the types in the order they are shown in the UI
+    pub fn next(self) -> ValueType {
+        match self {
+            ValueType::Str(_) => ValueType::I8,
+            ValueType::I8 => ValueType::I16,
+            ValueType::I16 => ValueType::I32,
+            ValueType::I32 => ValueType::I64,
+            ValueType::I64 => ValueType::U8,
+            ValueType::U8 => ValueType::U16,
+            ValueType::U16 => ValueType::U32,
+            ValueType::U32 => ValueType::U64,
+            ValueType::U64 => ValueType::F32,
+            ValueType::F32 => ValueType::F64,
+            ValueType::F64 => ValueType::Aob,
+            ValueType::Aob => ValueType::Regex,
+            ValueType::Regex => ValueType::Str(StrEncoding::Utf8),
+        }
+    }
+
+    /// The type with this `Display` name, e.g. `i32` or `utf16le`
+    pub fn from_name(name: &str) -> Result<ValueType, String> {
+        let mut value_type = ValueType::Str(StrEncoding::Utf8);
+        loop {
+            if value_type.to_string() == name {
+                return Ok(value_type);
+            }
+
+            value_type = match value_type {
+                ValueType::Str(encoding) if encoding.next() != StrEncoding::Utf8 => {
+                    ValueType::Str(encoding.next())
+                }
+                ValueType::Regex => return Err(format!("Unknown type '{}'", name)),
+                other => other.next(),
+            };
+        }
+    }
+
+    /// Size of the value in bytes, `None` for variable length types
+    pub fn size(self) -> Option<usize> {
+        match self {
+            ValueType::Str(_) | ValueType::Aob | ValueType::Regex => None,
+            ValueType::I8 | ValueType::U8 => Some(1),
+            ValueType::I16 | ValueType::U16 => Some(2),
+            ValueType::I32 | ValueType::U32 | ValueType::F32 => Some(4),
+            ValueType::I64 | ValueType::U64 | ValueType::F64 => Some(8),
+        }
+    }
+
+    pub fn is_numeric(self) -> bool {
+        !matches!(self, ValueType::Str(_) | ValueType::Aob | ValueType::Regex)
+    }
+
+    /// Parses user input as a number of this type
+    pub fn parse_number(self, input: &str) -> Result<Number, String> {
+        let input = input.trim();
+        match self {
+            ValueType::Str(_) | ValueType::Aob | ValueType::Regex => {
+                Err(format!("{} values can't be compared as numbers", self))
+            }
+            ValueType::F32 | ValueType::F64 => parse_float(input).map(Number::Float),
+            _ => parse_int(input).map(Number::Int),
+        }
+    }
+
+    /// Decodes bytes read from memory as a number of this type
+    pub fn number(self, bytes: &[u8], endian: Endianness) -> Option<Number> {
+        match self {
+            ValueType::Str(_) | ValueType::Aob | ValueType::Regex => None,
+            ValueType::I8 => from_bytes!(i8, bytes, endian).map(|v| Number::Int(v.into())),
+            ValueType::I16 => from_bytes!(i16, bytes, endian).map(|v| Number::Int(v.into())),
+            ValueType::I32 => from_bytes!(i32, bytes, endian).map(|v| Number::Int(v.into())),
+            ValueType::I64 => from_bytes!(i64, bytes, endian).map(|v| Number::Int(v.into())),
+            ValueType::U8 => from_bytes!(u8, bytes, endian).map(|v| Number::Int(v.into())),
+            ValueType::U16 => from_bytes!(u16, bytes, endian).map(|v| Number::Int(v.into())),
+            ValueType::U32 => from_bytes!(u32, bytes, endian).map(|v| Number::Int(v.into())),
+            ValueType::U64 => from_bytes!(u64, bytes, endian).map(|v| Number::Int(v.into())),
+            ValueType::F32 => from_bytes!(f32, bytes, endian).map(|v| Number::Float(v.into())),
+            ValueType::F64 => from_bytes!(f64, bytes, endian).map(Number::Float),
+        }
+    }
+
+    /// Encodes the user input into the bytes that are searched for
+    pub fn encode(self, input: &str, endian: Endianness) -> Result<Vec<u8>, String> {
+        let bytes = match self {
+            // Whitespace is part of strings, but not of numbers
+            ValueType::Str(encoding) => return encoding.encode(input),
+            // Regex matches are plain strings once found
+            ValueType::Regex => return Ok(input.as_bytes().to_vec()),
+            ValueType::Aob => {
+                let pattern = Pattern::parse(input.trim())?;
+                if pattern.has_wildcards() {
+                    return Err("Wildcards can only be used when searching".to_string());
+                }
+                return Ok(pattern.bytes);
+            }
+            ValueType::F32 => {
+                let input = input.trim();
+                let value = parse_float(input)?;
+                if value.is_finite() && (value as f32).is_infinite() {
+                    return Err(format!("{} doesn't fit in {}", input, self));
+                }
+                to_bytes!(value as f32, endian)
+            }
+            ValueType::F64 => to_bytes!(parse_float(input.trim())?, endian),
+            _ => {
+                let input = input.trim();
+                let n = parse_int(input)?;
+                let out_of_range = |_| format!("{} doesn't fit in {}", input, self);
+                match self {
+                    ValueType::I8 => to_bytes!(i8::try_from(n).map_err(out_of_range)?, endian),
+                    ValueType::I16 => to_bytes!(i16::try_from(n).map_err(out_of_range)?, endian),
+                    ValueType::I32 => to_bytes!(i32::try_from(n).map_err(out_of_range)?, endian),
+                    ValueType::I64 => to_bytes!(i64::try_from(n).map_err(out_of_range)?, endian),
+                    ValueType::U8 => to_bytes!(u8::try_from(n).map_err(out_of_range)?, endian),
+                    ValueType::U16 => to_bytes!(u16::try_from(n).map_err(out_of_range)?, endian),
+                    ValueType::U32 => to_bytes!(u32::try_from(n).map_err(out_of_range)?, endian),
+                    ValueType::U64 => to_bytes!(u64::try_from(n).map_err(out_of_range)?, endian),
+                    _ => unreachable!(),
+                }
+            }
+        };
+
+        Ok(bytes)
+    }
+
+    /// Decodes bytes read from memory into a printable value
+    pub fn decode(self, bytes: &[u8], endian: Endianness) -> String {
+        let value = match self {
+            ValueType::Str(encoding) => return encoding.decode(bytes),
+            ValueType::Regex => return bytes_to_string(bytes.to_vec()),
+            ValueType::Aob => return bytes_to_hex(bytes),
+            ValueType::I8 => from_bytes!(i8, bytes, endian).map(|v| v.to_string()),
+            ValueType::I16 => from_bytes!(i16, bytes, endian).map(|v| v.to_string()),
+            ValueType::I32 => from_bytes!(i32, bytes, endian).map(|v| v.to_string()),
+            ValueType::I64 => from_bytes!(i64, bytes, endian).map(|v| v.to_string()),
+            ValueType::U8 => from_bytes!(u8, bytes, endian).map(|v| v.to_string()),
+            ValueType::U16 => from_bytes!(u16, bytes, endian).map(|v| v.to_string()),
+            ValueType::U32 => from_bytes!(u32, bytes, endian).map(|v| v.to_string()),
+            ValueType::U64 => from_bytes!(u64, bytes, endian).map(|v| v.to_string()),
+            ValueType::F32 => from_bytes!(f32, bytes, endian).map(|v| v.to_string()),
+            ValueType::F64 => from_bytes!(f64, bytes, endian).map(|v| v.to_string()),
+        };
+
+        value.unwrap_or_else(|| "??".to_string())
+    }
+}
+
+impl fmt::Display for ValueType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ValueType::Str(encoding) => return write!(f, "{}", encoding),
+            ValueType::I8 => "i8",
+            ValueType::I16 => "i16",
+            ValueType::I32 => "i32",
+            ValueType::I64 => "i64",
+            ValueType::U8 => "u8",
+            ValueType::U16 => "u16",
+            ValueType::U32 => "u32",
+            ValueType::U64 => "u64",
+            ValueType::F32 => "f32",
+            ValueType::F64 => "f64",
+            ValueType::Aob => "aob",
+            ValueType::Regex => "regex",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl StrEncoding {
+    pub fn next(self) -> StrEncoding {
+        match self {
+            StrEncoding::Utf8 => StrEncoding::Utf16Le,
+            StrEncoding::Utf16Le => StrEncoding::Utf16Be,
+            StrEncoding::Utf16Be => StrEncoding::Latin1,
+            StrEncoding::Latin1 => StrEncoding::Utf8,
+        }
+    }
+
+    /// The zero code unit C strings end with
+    pub fn nul(self) -> Vec<u8> {
+        match self {
+            StrEncoding::Utf16Le | StrEncoding::Utf16Be => vec![0, 0],
+            StrEncoding::Utf8 | StrEncoding::Latin1 => vec![0],
+        }
+    }
+
+    /// Encodes a (piece of a) string without trimming it
+    pub fn encode(self, input: &str) -> Result<Vec<u8>, String> {
+        let bytes = match self {
+            StrEncoding::Utf8 => input.as_bytes().to_vec(),
+            StrEncoding::Utf16Le => input.encode_utf16().flat_map(u16::to_le_bytes).collect(),
+            StrEncoding::Utf16Be => input.encode_utf16().flat_map(u16::to_be_bytes).collect(),
+            StrEncoding::Latin1 => {
+                let mut bytes = Vec::new();
+                for c in input.chars() {
+                    match u8::try_from(c) {
+                        Ok(b) => bytes.push(b),
+                        Err(_) => return Err(format!("'{}' can't be encoded as latin-1", c)),
+                    }
+                }
+                bytes
+            }
+        };
+
+        Ok(bytes)
+    }
+
+    pub fn decode(self, bytes: &[u8]) -> String {
+        match self {
+            StrEncoding::Utf8 => bytes_to_string(bytes.to_vec()),
+            StrEncoding::Utf16Le | StrEncoding::Utf16Be => {
+                let units: Vec<u16> = bytes
+                    .chunks_exact(2)
+                    .map(|c| match self {
+                        StrEncoding::Utf16Le => u16::from_le_bytes([c[0], c[1]]),
+                        _ => u16::from_be_bytes([c[0], c[1]]),
+                    })
+                    .collect();
+                String::from_utf16_lossy(&units)
+            }
+            StrEncoding::Latin1 => bytes.iter().map(|b| *b as char).collect(),
+        }
+    }
+}
+
+impl fmt::Display for StrEncoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StrEncoding::Utf8 => write!(f, "utf8"),
+            StrEncoding::Utf16Le => write!(f, "utf16le"),
+            StrEncoding::Utf16Be => write!(f, "utf16be"),
+            StrEncoding::Latin1 => write!(f, "latin1"),
+        }
+    }
+}
+
+impl StrFill {
+    pub fn next(self) -> StrFill {
+        match self {
+            StrFill::AsTyped => StrFill::NulTerminated,
+            StrFill::NulTerminated => StrFill::ZeroPadded,
+            StrFill::ZeroPadded => StrFill::AsTyped,
+        }
+    }
+
+    /// Fills a string typed over a `length` byte value, `nul` is the encoding's terminator
+    pub fn apply(self, bytes: &mut Vec<u8>, nul: &[u8], length: usize) {
+        match self {
+            // A string filling the whole value has no room for one, like strncpy
+            StrFill::NulTerminated if bytes.len() + nul.len() <= length => {
+                bytes.extend_from_slice(nul)
+            }
+            StrFill::ZeroPadded if bytes.len() < length => bytes.resize(length, 0),
+            _ => {}
+        }
+    }
+}
+
+impl fmt::Display for StrFill {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StrFill::AsTyped => write!(f, "as typed"),
+            StrFill::NulTerminated => write!(f, "NUL terminated"),
+            StrFill::ZeroPadded => write!(f, "zero padded"),
+        }
+    }
+}
+
+impl Endianness {
+    /// Parses the `Display` form, `LE` or `BE`
+    pub fn from_name(name: &str) -> Result<Endianness, String> {
+        match name {
+            "LE" => Ok(Endianness::Little),
+            "BE" => Ok(Endianness::Big),
+            _ => Err(format!("Unknown endianness '{}'", name)),
+        }
+    }
+
+    pub fn toggle(self) -> Endianness {
+        match self {
+            Endianness::Little => Endianness::Big,
+            Endianness::Big => Endianness::Little,
+        }
+    }
+}
+
+impl fmt::Display for Endianness {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Endianness::Little => write!(f, "LE"),
+            Endianness::Big => write!(f, "BE"),
+        }
+    }
+}
+
+/// Parses decimal or `0x` prefixed hex integers (with an optional sign)
+fn parse_int(input: &str) -> Result<i128, String> {
+    let (negative, digits) = match input.strip_prefix('-') {
+        Some(d) => (true, d),
+        None => (false, input),
+    };
+
+    let parsed = match digits
+        .strip_prefix("0x")
+        .or_else(|| digits.strip_prefix("0X"))
+    {
+        Some(hex) => i128::from_str_radix(hex, 16),
+        None => digits.parse::<i128>(),
+    };
+
+    match parsed {
+        Ok(n) if negative => Ok(-n),
+        Ok(n) => Ok(n),
+        Err(_) => Err(format!("{} is not an integer", input)),
+    }
+}
+
+fn parse_float(input: &str) -> Result<f64, String> {
+    input
+        .parse::<f64>()
+        .map_err(|_| format!("{} is not a float", input))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_int_range() {
+        let le = Endianness::Little;
+        assert_eq!(ValueType::I8.encode("-128", le), Ok(vec![0x80]));
+        assert_eq!(ValueType::U8.encode("255", le), Ok(vec![0xFF]));
+        assert!(ValueType::I8.encode("128", le).is_err());
+        assert!(ValueType::U8.encode("-1", le).is_err());
+        assert!(ValueType::U16.encode("0x10000", le).is_err());
+        assert!(ValueType::I64.encode("9223372036854775808", le).is_err());
+        assert_eq!(
+            ValueType::U64.encode("18446744073709551615", le),
+            Ok(vec![0xFF; 8])
+        );
+    }
+
+    #[test]
+    fn encode_float_range() {
+        let le = Endianness::Little;
+        assert!(ValueType::F32.encode("1e39", le).is_err());
+        assert!(ValueType::F32.encode("inf", le).is_ok());
+        assert!(ValueType::F64.encode("1e39", le).is_ok());
+        assert!(ValueType::F32.encode("abc", le).is_err());
+    }
+
+    #[test]
+    fn encode_endianness() {
+        assert_eq!(
+            ValueType::U32.encode("0x11223344", Endianness::Little),
+            Ok(vec![0x44, 0x33, 0x22, 0x11])
+        );
+        assert_eq!(
+            ValueType::U32.encode("0x11223344", Endianness::Big),
+            Ok(vec![0x11, 0x22, 0x33, 0x44])
+        );
+        assert_eq!(
+            ValueType::F32.encode("1", Endianness::Big),
+            Ok(vec![0x3F, 0x80, 0, 0])
+        );
+    }
+
+    #[test]
+    fn decode_round_trips() {
+        for endian in [Endianness::Little, Endianness::Big] {
+            let bytes = ValueType::I16.encode("-2", endian).unwrap();
+            assert_eq!(ValueType::I16.decode(&bytes, endian), "-2");
+        }
+        assert_eq!(ValueType::I32.decode(&[1, 2], Endianness::Little), "??");
+    }
+
+    #[test]
+    fn str_fill_at_the_exact_length() {
+        let fill = |fill: StrFill, typed: &[u8], nul: &[u8], length| {
+            let mut bytes = typed.to_vec();
+            fill.apply(&mut bytes, nul, length);
+            bytes
+        };
+
+        assert_eq!(fill(StrFill::NulTerminated, b"abc", &[0], 4), b"abc\0");
+        // No room left for the NUL, the string fills the value
+        assert_eq!(fill(StrFill::NulTerminated, b"abcd", &[0], 4), b"abcd");
+        assert_eq!(fill(StrFill::NulTerminated, b"a\0", &[0, 0], 3), b"a\0");
+        assert_eq!(fill(StrFill::NulTerminated, b"a\0", &[0, 0], 4), b"a\0\0\0");
+
+        assert_eq!(fill(StrFill::ZeroPadded, b"ab", &[0], 4), b"ab\0\0");
+        assert_eq!(fill(StrFill::ZeroPadded, b"abcd", &[0], 4), b"abcd");
+        // Too long stays too long, the caller refuses it
+        assert_eq!(fill(StrFill::ZeroPadded, b"abcde", &[0], 4), b"abcde");
+
+        assert_eq!(fill(StrFill::AsTyped, b"ab", &[0], 4), b"ab");
+    }
+}